@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+/// A reduced ordered binary decision diagram, as a node of a `Manager`. Two
+/// BDDs of the same manager are equal exactly when they denote the same
+/// boolean function.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Bdd(u32);
+
+pub const FALSE: Bdd = Bdd(0);
+pub const TRUE: Bdd = Bdd(1);
+
+/// The variable of the terminals, which comes after all other variables:
+/// the largest `u32`.
+const TERMINAL: u32 = !0;
+
+/// Interleaves the lowest `bits` bits of a state `s` and a successor `t`
+/// into a key for the variables `0, 1, ..., 2 * bits - 1`.
+pub fn interleave(s: u64, t: u64, bits: u32) -> u64 {
+    (0..bits).fold(0, |key, i| {
+        key | (s >> i & 1) << (2 * i + 1) | (t >> i & 1) << (2 * i)
+    })
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Op {
+    And,
+    Or,
+    Not,
+    AndExists,
+    Prime,
+}
+
+#[derive(Clone, Copy)]
+struct Node {
+    var: u32,
+    low: Bdd,
+    high: Bdd,
+}
+
+/// Creates and combines BDDs over the variables `0, 1, ...`, in that order.
+/// Variables come in pairs: the even variable `2i` encodes a bit of a state
+/// and the odd variable `2i + 1` the same bit of a successor state.
+pub struct Manager {
+    nodes: Vec<Node>,
+    unique: HashMap<(u32, Bdd, Bdd), Bdd>,
+    computed: HashMap<(Op, Bdd, Bdd), Bdd>,
+}
+
+impl Manager {
+    pub fn new() -> Manager {
+        let terminal = |b| Node { var: TERMINAL, low: b, high: b };
+        Manager {
+            nodes: vec![terminal(FALSE), terminal(TRUE)],
+            unique: HashMap::new(),
+            computed: HashMap::new(),
+        }
+    }
+
+    fn node(&self, f: Bdd) -> Node {
+        self.nodes[f.0 as usize]
+    }
+
+    fn mk(&mut self, var: u32, low: Bdd, high: Bdd) -> Bdd {
+        if low == high {
+            return low;
+        }
+        let nodes = &mut self.nodes;
+        *self.unique.entry((var, low, high)).or_insert_with(|| {
+            nodes.push(Node { var, low, high });
+            Bdd(nodes.len() as u32 - 1)
+        })
+    }
+
+    /// The cofactors of `f` for `var` being false and true.
+    fn cofactors(&self, f: Bdd, var: u32) -> (Bdd, Bdd) {
+        let node = self.node(f);
+        match node.var == var {
+            true => (node.low, node.high),
+            false => (f, f),
+        }
+    }
+
+    /// The function that holds for exactly the `keys`, which must be sorted.
+    /// Variable `vars[i]` encodes bit `vars.len() - 1 - i` of a key, so the
+    /// most significant bit comes first.
+    pub fn of_keys(&mut self, keys: &[u64], vars: &[u32]) -> Bdd {
+        match (keys.is_empty(), vars.split_first()) {
+            (true, _) => FALSE,
+            (false, None) => TRUE,
+            (false, Some((&var, rest))) => {
+                let bit = 1 << rest.len();
+                let split = keys
+                    .iter()
+                    .position(|k| k & bit != 0)
+                    .unwrap_or(keys.len());
+                let low = self.of_keys(&keys[..split], rest);
+                let high = self.of_keys(&keys[split..], rest);
+                self.mk(var, low, high)
+            },
+        }
+    }
+
+    /// The keys for which `f` holds, in increasing order, with the bits
+    /// encoded by `vars` like in `of_keys`. `f` may only depend on
+    /// `vars`.
+    pub fn keys(&self, f: Bdd, vars: &[u32]) -> Vec<u64> {
+        let mut keys = Vec::new();
+        self.collect_keys(f, vars, 0, &mut keys);
+        keys
+    }
+
+    fn collect_keys(
+        &self,
+        f: Bdd,
+        vars: &[u32],
+        key: u64,
+        keys: &mut Vec<u64>,
+    ) {
+        match vars.split_first() {
+            _ if f == FALSE => (),
+            None => keys.push(key),
+            Some((&var, rest)) => {
+                let (low, high) = self.cofactors(f, var);
+                self.collect_keys(low, rest, key << 1, keys);
+                self.collect_keys(high, rest, key << 1 | 1, keys);
+            },
+        }
+    }
+
+    pub fn and(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        self.apply(Op::And, f, g)
+    }
+
+    pub fn or(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        self.apply(Op::Or, f, g)
+    }
+
+    pub fn not(&mut self, f: Bdd) -> Bdd {
+        match f {
+            FALSE => TRUE,
+            TRUE => FALSE,
+            _ => self.cached(Op::Not, f, FALSE, |m| {
+                let node = m.node(f);
+                let low = m.not(node.low);
+                let high = m.not(node.high);
+                m.mk(node.var, low, high)
+            }),
+        }
+    }
+
+    /// The relational product `∃ x'. f(x, x') ∧ g(x, x')`, quantifying all
+    /// odd variables.
+    pub fn and_exists(&mut self, f: Bdd, g: Bdd) -> Bdd {
+        if f == FALSE || g == FALSE {
+            return FALSE;
+        }
+        if f == TRUE && g == TRUE {
+            return TRUE;
+        }
+        let (f, g) = (f.min(g), f.max(g));
+        self.cached(Op::AndExists, f, g, |m| {
+            let var = m.node(f).var.min(m.node(g).var);
+            let (f0, f1) = m.cofactors(f, var);
+            let (g0, g1) = m.cofactors(g, var);
+            let low = m.and_exists(f0, g0);
+            match var % 2 {
+                0 => {
+                    let high = m.and_exists(f1, g1);
+                    m.mk(var, low, high)
+                },
+                _ if low == TRUE => TRUE,
+                _ => {
+                    let high = m.and_exists(f1, g1);
+                    m.or(low, high)
+                },
+            }
+        })
+    }
+
+    /// Replaces every even variable in `f` by the odd variable after it,
+    /// i.e. moves a set of states to the successor side of a relation. `f`
+    /// may only depend on even variables.
+    pub fn prime(&mut self, f: Bdd) -> Bdd {
+        match f {
+            FALSE | TRUE => f,
+            _ => self.cached(Op::Prime, f, FALSE, |m| {
+                let node = m.node(f);
+                let low = m.prime(node.low);
+                let high = m.prime(node.high);
+                m.mk(node.var + 1, low, high)
+            }),
+        }
+    }
+
+    fn apply(&mut self, op: Op, f: Bdd, g: Bdd) -> Bdd {
+        let (f, g) = (f.min(g), f.max(g));
+        match (op, f, g) {
+            (Op::And, FALSE, _) => return FALSE,
+            (Op::And, TRUE, _) => return g,
+            (Op::Or, FALSE, _) => return g,
+            (Op::Or, TRUE, _) => return TRUE,
+            _ if f == g => return f,
+            _ => (),
+        }
+        self.cached(op, f, g, |m| {
+            let var = m.node(f).var.min(m.node(g).var);
+            let (f0, f1) = m.cofactors(f, var);
+            let (g0, g1) = m.cofactors(g, var);
+            let low = m.apply(op, f0, g0);
+            let high = m.apply(op, f1, g1);
+            m.mk(var, low, high)
+        })
+    }
+
+    fn cached(
+        &mut self,
+        op: Op,
+        f: Bdd,
+        g: Bdd,
+        compute: impl FnOnce(&mut Manager) -> Bdd,
+    ) -> Bdd {
+        if let Some(&r) = self.computed.get(&(op, f, g)) {
+            return r;
+        }
+        let r = compute(self);
+        self.computed.insert((op, f, g), r);
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations() {
+        let mut m = Manager::new();
+        let vars = [0, 2, 4];
+        let a = m.of_keys(&[1, 2, 5], &vars);
+        let b = m.of_keys(&[2, 3, 5, 7], &vars);
+        let and = m.and(a, b);
+        assert_eq!(m.keys(and, &vars), [2, 5]);
+        let or = m.or(a, b);
+        assert_eq!(m.keys(or, &vars), [1, 2, 3, 5, 7]);
+        let not = m.not(a);
+        assert_eq!(m.keys(not, &vars), [0, 3, 4, 6, 7]);
+
+        // Functions are canonical.
+        let c = m.of_keys(&[2, 5], &vars);
+        assert_eq!(and, c);
+        let all = m.or(a, not);
+        assert_eq!(all, TRUE);
+
+        // The predecessors of {2, 3} in {0 -> 2, 1 -> 1, 3 -> 3, 3 -> 2}.
+        let pairs = [(0, 2), (1, 1), (3, 3), (3, 2)];
+        let mut keys =
+            pairs.iter().map(|&(s, t)| interleave(s, t, 2)).collect::<Vec<_>>();
+        keys.sort();
+        let relation = m.of_keys(&keys, &[0, 1, 2, 3]);
+        let targets = m.of_keys(&[2, 3], &[0, 2]);
+        let targets = m.prime(targets);
+        let pre = m.and_exists(relation, targets);
+        assert_eq!(m.keys(pre, &[0, 2]), [0, 3]);
+    }
+}
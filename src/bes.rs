@@ -0,0 +1,261 @@
+use crate::{
+    game::{Game, Player},
+    lts,
+};
+use std::{collections::BTreeSet, fmt};
+
+/// A boolean equation system with a variable for every vertex `(state,
+/// subformula)` of a game. There is a block of equations for every fixpoint
+/// of the formula, ordered from the outermost to the innermost fixpoint.
+#[derive(Clone, Debug)]
+pub struct Bes {
+    pub blocks: Vec<Block>,
+    pub init: usize,
+    /// The state and subformula each variable stands for.
+    pub vars: Vec<(lts::State, usize)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub sign: Sign,
+    pub equations: Vec<Equation>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Sign {
+    Mu,
+    Nu,
+}
+
+#[derive(Clone, Debug)]
+pub struct Equation {
+    pub var: usize,
+    pub rhs: Expr,
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Expr {
+    True,
+    False,
+    Var(usize),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl Bes {
+    /// Reads off the equations from the moves of `game`: a vertex of `Even`
+    /// is a disjunction, a vertex of `Odd` a conjunction.
+    pub fn new(game: &Game, init: lts::State) -> Bes {
+        Bes::partial(game, init, false)
+    }
+
+    /// Like `new`, but for a partially explored game: the variables of
+    /// vertices whose moves are unknown are fixed to `unexplored`. Since the
+    /// solution is monotone in these, it bounds the solution of the whole
+    /// game from below if `unexplored` is false, and from above otherwise.
+    pub fn partial(game: &Game, init: lts::State, unexplored: bool) -> Bes {
+        // Subformulas outside of any fixpoint go into a leading block; its
+        // sign is irrelevant, as they do not depend on themselves.
+        let fixpoints = game
+            .fixpoints
+            .iter()
+            .enumerate()
+            .filter(|&(i, &fixpoint)| fixpoint == Some(i))
+            .map(|(i, _)| Some(i));
+        let order = std::iter::once(None)
+            .chain(fixpoints)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut blocks = order
+            .iter()
+            .map(|fixpoint| Block {
+                sign: match fixpoint {
+                    Some(i) if game.subformulas[*i].is_mu() => Sign::Mu,
+                    _ => Sign::Nu,
+                },
+                equations: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        for (v, &(_, i)) in game.vertices.iter().enumerate() {
+            let block = order.binary_search(&game.fixpoints[i]).unwrap();
+            blocks[block]
+                .equations
+                .push(Equation { var: v, rhs: rhs(game, v, unexplored) });
+        }
+        blocks.retain(|block| !block.equations.is_empty());
+
+        Bes { blocks, init: game.vertex(init), vars: game.vertices.clone() }
+    }
+
+    fn fmt_expr(&self, fmt: &mut fmt::Formatter, expr: &Expr) -> fmt::Result {
+        let (op, es) = match expr {
+            Expr::True => return write!(fmt, "true"),
+            Expr::False => return write!(fmt, "false"),
+            Expr::Var(x) => return self.fmt_var(fmt, *x),
+            Expr::And(es) => (" && ", es),
+            Expr::Or(es) => (" || ", es),
+        };
+        for (j, e) in es.iter().enumerate() {
+            if j > 0 {
+                write!(fmt, "{}", op)?;
+            }
+            match e {
+                Expr::And(_) | Expr::Or(_) => {
+                    write!(fmt, "(")?;
+                    self.fmt_expr(fmt, e)?;
+                    write!(fmt, ")")?;
+                },
+                _ => self.fmt_expr(fmt, e)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn fmt_var(&self, fmt: &mut fmt::Formatter, x: usize) -> fmt::Result {
+        let (s, i) = self.vars[x];
+        write!(fmt, "X{}_{}", s, i)
+    }
+}
+
+fn rhs(game: &Game, v: usize, unexplored: bool) -> Expr {
+    if v >= game.explored {
+        return match unexplored {
+            true => Expr::True,
+            false => Expr::False,
+        };
+    }
+    let vars = || game.succ[v].iter().map(|&w| Expr::Var(w)).collect();
+    match (&game.succ[v][..], game.owner[v]) {
+        (&[w], Player::Even) if w == v => Expr::False,
+        (&[w], Player::Odd) if w == v => Expr::True,
+        (&[w], _) => Expr::Var(w),
+        (_, Player::Even) => Expr::Or(vars()),
+        (_, Player::Odd) => Expr::And(vars()),
+    }
+}
+
+impl Expr {
+    /// Replaces `x` by `by`, simplifying the result.
+    pub fn substitute(&self, x: usize, by: &Expr) -> Expr {
+        match self {
+            Expr::Var(y) if *y == x => by.clone(),
+            Expr::And(es) =>
+                junction(es.iter().map(|e| e.substitute(x, by)), true),
+            Expr::Or(es) =>
+                junction(es.iter().map(|e| e.substitute(x, by)), false),
+            e => e.clone(),
+        }
+    }
+
+    /// Adds the variables occurring in the expression to `vars`.
+    pub fn variables(&self, vars: &mut Vec<usize>) {
+        match self {
+            Expr::Var(x) => vars.push(*x),
+            Expr::And(es) | Expr::Or(es) =>
+                es.iter().for_each(|e| e.variables(vars)),
+            Expr::True | Expr::False => (),
+        }
+    }
+
+    /// Evaluates the expression, given the values of its variables.
+    pub fn eval(&self, values: &[bool]) -> bool {
+        match self {
+            Expr::True => true,
+            Expr::False => false,
+            Expr::Var(x) => values[*x],
+            Expr::And(es) => es.iter().all(|e| e.eval(values)),
+            Expr::Or(es) => es.iter().any(|e| e.eval(values)),
+        }
+    }
+}
+
+/// Builds a conjunction or disjunction of `es`, flattening nested ones and
+/// removing constants and duplicates.
+fn junction(es: impl Iterator<Item = Expr>, conj: bool) -> Expr {
+    let (unit, zero) = match conj {
+        true => (Expr::True, Expr::False),
+        false => (Expr::False, Expr::True),
+    };
+    let mut operands = Vec::new();
+    for e in es {
+        match e {
+            e if e == unit => (),
+            e if e == zero => return zero,
+            Expr::And(fs) if conj => operands.extend(fs),
+            Expr::Or(fs) if !conj => operands.extend(fs),
+            e => operands.push(e),
+        }
+    }
+    operands.sort();
+    operands.dedup();
+    match operands.len() {
+        0 => unit,
+        1 => operands.pop().unwrap(),
+        _ if conj => Expr::And(operands),
+        _ => Expr::Or(operands),
+    }
+}
+
+impl fmt::Display for Bes {
+    /// Writes the equation system in the textual format of mCRL2.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "pbes")?;
+        for block in &self.blocks {
+            let sign = match block.sign {
+                Sign::Mu => "mu",
+                Sign::Nu => "nu",
+            };
+            for eq in &block.equations {
+                write!(fmt, "  {} ", sign)?;
+                self.fmt_var(fmt, eq.var)?;
+                write!(fmt, " = ")?;
+                self.fmt_expr(fmt, &eq.rhs)?;
+                writeln!(fmt, ";")?;
+            }
+        }
+        write!(fmt, "init ")?;
+        self.fmt_var(fmt, self.init)?;
+        writeln!(fmt, ";")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lts::Lts, mu_calculus as mc};
+
+    #[test]
+    fn mcrl2_format() {
+        let lts = "des (0,1,2)\n(0,\"a\",1)".parse::<Lts>().unwrap();
+        let f = "nu Y. [a]Y && mu X. (<a>X || [a]false)"
+            .parse::<mc::Formula>()
+            .unwrap();
+        let game = Game::new(&lts, &f, &mc::Valuation::new());
+        let bes = Bes::new(&game, lts.init());
+        assert_eq!(bes.blocks.len(), 2);
+        assert_eq!(
+            bes.to_string(),
+            "pbes
+  nu X0_0 = X0_1;
+  nu X1_0 = X1_1;
+  nu X0_1 = X0_2 && X0_4;
+  nu X1_1 = X1_2 && X1_4;
+  nu X0_2 = X1_3;
+  nu X1_2 = true;
+  nu X1_3 = X1_0;
+  mu X0_4 = X0_5;
+  mu X1_4 = X1_5;
+  mu X0_5 = X0_6 || X0_8;
+  mu X1_5 = X1_6 || X1_8;
+  mu X0_6 = X1_7;
+  mu X0_8 = X1_9;
+  mu X1_6 = false;
+  mu X1_8 = true;
+  mu X1_7 = X1_4;
+  mu X1_9 = false;
+init X0_0;
+"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    gauss, improved, jurdzinski, linear, local,
+    lts::{self, Lts},
+    mu_calculus as mc, naive,
+    stats::Stats,
+    strategy_improvement, symbolic, tableau, witness, zielonka, MyuError,
+};
+use std::time::Instant;
+
+/// All model checkers, in the order in which `--list-algorithms` shows them.
+pub const ALGORITHMS: &[&dyn ModelChecker] = &[
+    &naive::Naive,
+    &improved::EmersonLei,
+    &linear::Linear,
+    &zielonka::Zielonka,
+    &jurdzinski::SmallProgressMeasures,
+    &strategy_improvement::StrategyImprovement,
+    &gauss::Gauss,
+    &local::Local,
+    &tableau::Tableau,
+    &symbolic::Symbolic,
+];
+
+/// The options of a run, which every model checker reads as far as they
+/// concern it.
+pub struct Options<'a> {
+    /// The states assigned to the free variables of the formula.
+    pub valuation: &'a mc::Valuation,
+    /// Whether the Emerson-Lei algorithm only redoes the work for states
+    /// affected by the previous iteration of a fixpoint.
+    pub delta: bool,
+}
+
+/// The outcome of a run of a model checker.
+pub struct Outcome {
+    /// The states satisfying the formula. Checkers that only decide the
+    /// initial state return at most that state.
+    pub result: lts::StateSet,
+    pub stats: Stats,
+}
+
+/// An algorithm deciding which states of an LTS satisfy a formula.
+pub trait ModelChecker: Sync {
+    /// The name by which `--algorithm` selects the checker.
+    fn name(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// What the steps counted in `Stats::steps` are.
+    fn steps(&self) -> &'static str;
+
+    /// Fails if the checker cannot handle `f`.
+    fn supports(&self, _f: &mc::Formula) -> Result<(), MyuError> {
+        Ok(())
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats);
+
+    /// Explains whether the initial state satisfies `f`, as given by
+    /// `holds`.
+    fn witness(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+        holds: bool,
+    ) -> witness::Witness {
+        witness::Witness::new(lts, f, options.valuation, holds)
+    }
+
+    fn check(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> Result<Outcome, MyuError> {
+        self.supports(f)?;
+        let start = Instant::now();
+        let (result, mut stats) = self.eval(lts, f, options);
+        stats.time = start.elapsed();
+        Ok(Outcome { result, stats })
+    }
+}
+
+/// Looks up a model checker by its name.
+pub fn find(name: &str) -> Result<&'static dyn ModelChecker, String> {
+    ALGORITHMS
+        .iter()
+        .cloned()
+        .find(|checker| checker.name() == name)
+        .ok_or_else(|| {
+            format!("unknown algorithm {:?}, see --list-algorithms", name)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        for checker in ALGORITHMS {
+            assert_eq!(find(checker.name()).unwrap().name(), checker.name());
+        }
+        assert!(find("dijkstra").is_err());
+    }
+
+    #[test]
+    fn supports() {
+        let f = "nu X. mu Y. (<a>X || <b>Y)".parse::<mc::Formula>().unwrap();
+        assert_eq!(
+            linear::Linear.supports(&f),
+            Err(MyuError::AlternationError(2))
+        );
+        assert!(improved::EmersonLei.supports(&f).is_ok());
+    }
+}
@@ -0,0 +1,374 @@
+use crate::{
+    lts::{self, Lts},
+    mu_calculus as mc,
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::Write,
+};
+
+/// The players of a parity game: `Even` tries to show that a formula holds,
+/// `Odd` that it does not.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Player {
+    Even = 0,
+    Odd = 1,
+}
+
+/// A max-parity game with vertices `(state, subformula)`. The subformulas
+/// are those of the formula in positive normal form, with regular modalities
+/// lowered to fixpoints. A player that cannot move loses; such vertices get a
+/// self-loop with a priority of their opponent's parity.
+#[derive(Clone, Debug, Default)]
+pub struct Game {
+    pub subformulas: Vec<mc::Formula>,
+    pub vertices: Vec<(lts::State, usize)>,
+    pub owner: Vec<Player>,
+    pub priority: Vec<u32>,
+    pub succ: Vec<Vec<usize>>,
+    pub pred: Vec<Vec<usize>>,
+    /// The innermost fixpoint containing each subformula, where fixpoints
+    /// contain themselves.
+    pub fixpoints: Vec<Option<usize>>,
+    /// The subformulas a subformula steps to; for a bound variable this is
+    /// its binder.
+    children: Vec<Vec<usize>>,
+    /// Priorities of subformulas, which are non-zero only for fixpoints.
+    priorities: Vec<u32>,
+    index: HashMap<(lts::State, usize), usize>,
+    /// The number of vertices whose moves have been explored. Vertices are
+    /// explored in the order in which they are added.
+    pub explored: usize,
+}
+
+/// The winner of every vertex of a game, together with a strategy that
+/// picks a move for every vertex owned by the player winning it.
+#[derive(Clone, Debug)]
+pub struct Solution {
+    pub winner: Vec<Player>,
+    pub strategy: Vec<Option<usize>>,
+}
+
+impl Player {
+    /// The player that wins plays in which `priority` is the highest one
+    /// occurring infinitely often.
+    pub fn of_priority(priority: u32) -> Player {
+        match priority % 2 {
+            0 => Player::Even,
+            _ => Player::Odd,
+        }
+    }
+
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::Even => Player::Odd,
+            Player::Odd => Player::Even,
+        }
+    }
+}
+
+impl Game {
+    /// Constructs the game for `f` on all states of `lts`. Free variables of
+    /// `f` are interpreted according to `valuation`.
+    pub fn new(lts: &Lts, f: &mc::Formula, valuation: &mc::Valuation) -> Game {
+        Game::explore(lts, f, valuation, lts.states().iter())
+    }
+
+    /// Constructs only the vertex for `f` in state `s`; the part of the game
+    /// reachable from it is explored step by step with `explore_next`.
+    pub fn local(f: &mc::Formula, s: lts::State) -> Game {
+        Game::unexplored(f, std::iter::once(s))
+    }
+
+    fn explore(
+        lts: &Lts,
+        f: &mc::Formula,
+        valuation: &mc::Valuation,
+        roots: impl Iterator<Item = lts::State>,
+    ) -> Game {
+        let mut game = Game::unexplored(f, roots);
+        while game.explore_next(lts, valuation) {}
+
+        game.pred = vec![Vec::new(); game.vertices.len()];
+        for (v, succ) in game.succ.iter().enumerate() {
+            for &w in succ {
+                game.pred[w].push(v);
+            }
+        }
+        game
+    }
+
+    fn unexplored(
+        f: &mc::Formula,
+        roots: impl Iterator<Item = lts::State>,
+    ) -> Game {
+        let f = f.to_pnf().lower_regular().rename_bound();
+        let mut game = Game::default();
+        game.add_subformula(&f, None, &mut Vec::new());
+        for s in roots {
+            game.add_vertex(s, 0);
+        }
+        game
+    }
+
+    /// Explores the moves of the next unexplored vertex, adding the vertices
+    /// it moves to. Returns false if all vertices have been explored. The
+    /// predecessors of vertices are not maintained.
+    pub fn explore_next(
+        &mut self,
+        lts: &Lts,
+        valuation: &mc::Valuation,
+    ) -> bool {
+        let v = self.explored;
+        if v == self.vertices.len() {
+            return false;
+        }
+        self.explored += 1;
+        let (s, i) = self.vertices[v];
+        let (owner, targets) = self.moves(lts, valuation, s, i);
+        let succ = targets
+            .into_iter()
+            .map(|(t, j)| self.add_vertex(t, j))
+            .collect::<Vec<_>>();
+        self.owner[v] = owner;
+        if succ.is_empty() {
+            self.succ[v] = vec![v];
+            self.priority[v] = match owner {
+                Player::Even => 1,
+                Player::Odd => 0,
+            };
+        } else {
+            self.succ[v] = succ;
+            self.priority[v] = self.priorities[i];
+        }
+        true
+    }
+
+    /// The vertex standing for the whole formula in state `s`.
+    pub fn vertex(&self, s: lts::State) -> usize {
+        self.index[&(s, 0)]
+    }
+
+    /// The states in which the formula holds according to `solution`.
+    pub fn winning_states(&self, solution: &Solution) -> lts::StateSet {
+        self.vertices
+            .iter()
+            .zip(&solution.winner)
+            .filter(|((_, i), &winner)| *i == 0 && winner == Player::Even)
+            .map(|((s, _), _)| *s)
+            .collect()
+    }
+
+    /// Writes the game in the format of PGSolver, naming vertices by their
+    /// state and subformula.
+    pub fn to_pgsolver(&self, init: lts::State) -> String {
+        let mut gm = format!(
+            "parity {};\nstart {};\n",
+            self.vertices.len().saturating_sub(1),
+            self.vertex(init)
+        );
+        for (v, &(s, i)) in self.vertices.iter().enumerate() {
+            let succ = self.succ[v]
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            let name = format!("{} ⊨ {}", s, self.subformulas[i]);
+            writeln!(
+                gm,
+                "{} {} {} {} \"{}\";",
+                v,
+                self.priority[v],
+                self.owner[v] as u8,
+                succ,
+                name.replace('"', "\\\"")
+            )
+            .unwrap();
+        }
+        gm
+    }
+
+    /// Adds `f` and its subformulas, returning the index of `f` and the
+    /// highest priority of a fixpoint within it.
+    fn add_subformula(
+        &mut self,
+        f: &mc::Formula,
+        fixpoint: Option<usize>,
+        scope: &mut Vec<(mc::VarName, usize)>,
+    ) -> (usize, Option<u32>) {
+        use mc::Formula::*;
+        let i = self.subformulas.len();
+        let fixpoint = if f.is_mu() || f.is_nu() { Some(i) } else { fixpoint };
+        self.subformulas.push(f.clone());
+        self.fixpoints.push(fixpoint);
+        self.children.push(Vec::new());
+        self.priorities.push(0);
+
+        let (children, inner) = match f {
+            Var { name } => {
+                let binder = scope.iter().rev().find(|(var, _)| var == name);
+                (binder.map(|&(_, j)| j).into_iter().collect(), None)
+            },
+            True | False | Not { .. } => (Vec::new(), None),
+            And { f1, f2 } | Or { f1, f2 } => {
+                let (j1, p1) = self.add_subformula(f1, fixpoint, scope);
+                let (j2, p2) = self.add_subformula(f2, fixpoint, scope);
+                (vec![j1, j2], p1.max(p2))
+            },
+            Diamond { f: g, .. } | Box { f: g, .. } => {
+                let (j, p) = self.add_subformula(g, fixpoint, scope);
+                (vec![j], p)
+            },
+            Mu { var, f: g } | Nu { var, f: g } => {
+                scope.push((var.clone(), i));
+                let (j, p) = self.add_subformula(g, fixpoint, scope);
+                scope.pop();
+                // Fixpoints get the lowest priority of their parity that
+                // dominates all fixpoints nested within them.
+                let parity = if f.is_nu() { 0 } else { 1 };
+                let priority = match p {
+                    Some(p) if p % 2 == parity => p,
+                    Some(p) => p + 1,
+                    None => parity,
+                };
+                self.priorities[i] = priority;
+                (vec![j], Some(priority))
+            },
+            Implies { .. } | Iff { .. } =>
+                unreachable!("formula is not in positive normal form"),
+        };
+        self.children[i] = children;
+        (i, inner)
+    }
+
+    fn add_vertex(&mut self, s: lts::State, i: usize) -> usize {
+        if let Some(&v) = self.index.get(&(s, i)) {
+            return v;
+        }
+        let v = self.vertices.len();
+        self.vertices.push((s, i));
+        self.owner.push(Player::Even);
+        self.priority.push(0);
+        self.succ.push(Vec::new());
+        self.index.insert((s, i), v);
+        v
+    }
+
+    /// The owner of vertex `(s, i)` and the vertices it can move to.
+    fn moves(
+        &self,
+        lts: &Lts,
+        valuation: &mc::Valuation,
+        s: lts::State,
+        i: usize,
+    ) -> (Player, Vec<(lts::State, usize)>) {
+        use mc::Formula::*;
+        let children = &self.children[i];
+        let dead_end = |holds: bool| match holds {
+            true => (Player::Odd, Vec::new()),
+            false => (Player::Even, Vec::new()),
+        };
+
+        match &self.subformulas[i] {
+            True => dead_end(true),
+            False => dead_end(false),
+            Var { name } if children.is_empty() =>
+                dead_end(valuation[name].contains(&s)),
+            Not { f } => match &**f {
+                Var { name } => dead_end(!valuation[name].contains(&s)),
+                _ => unreachable!("formula is not in positive normal form"),
+            },
+            Var { .. } | Mu { .. } | Nu { .. } =>
+                (Player::Even, vec![(s, children[0])]),
+            And { .. } =>
+                (Player::Odd, children.iter().map(|&j| (s, j)).collect()),
+            Or { .. } =>
+                (Player::Even, children.iter().map(|&j| (s, j)).collect()),
+            Diamond { step, .. } | Box { step, .. } => {
+                let act = match step {
+                    mc::RegFormula::Action { act } => act,
+                    _ => unreachable!("regular formulas should be lowered"),
+                };
+                let targets = lts
+                    .successors(s)
+                    .filter(|(l, _)| act.matches(l))
+                    .map(|(_, t)| (t, children[0]))
+                    .collect::<BTreeSet<_>>();
+                let owner = match self.subformulas[i] {
+                    Diamond { .. } => Player::Even,
+                    _ => Player::Odd,
+                };
+                (owner, targets.into_iter().collect())
+            },
+            Implies { .. } | Iff { .. } =>
+                unreachable!("formula is not in positive normal form"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn construction() {
+        let lts =
+            "des (0,2,2)\n(0,\"a\",1)\n(1,\"b\",1)".parse::<Lts>().unwrap();
+        let f = "nu X. mu Y. (<a>X || [b]Y)".parse::<mc::Formula>().unwrap();
+        let game = Game::new(&lts, &f, &mc::Valuation::new());
+
+        let vertex = |s, g: &str| {
+            let g = g.parse::<mc::Formula>().unwrap();
+            let i = game.subformulas.iter().position(|h| *h == g).unwrap();
+            game.index[&(s, i)]
+        };
+        assert_eq!(game.priority[game.vertex(0)], 2);
+        assert_eq!(game.priority[vertex(0, "mu Y. (<a>X || [b]Y)")], 1);
+        assert_eq!(game.owner[vertex(1, "(<a>X || [b]Y)")], Player::Even);
+        assert_eq!(game.owner[vertex(1, "[b]Y")], Player::Odd);
+        assert_eq!(game.succ[vertex(0, "<a>X")], vec![vertex(1, "X")]);
+        assert_eq!(
+            game.succ[vertex(1, "Y")],
+            vec![vertex(1, "mu Y. (<a>X || [b]Y)")]
+        );
+
+        // Moving along `[b]` is impossible in state 0, so Odd loses there.
+        let v = vertex(0, "[b]Y");
+        assert_eq!(game.succ[v], vec![v]);
+        assert_eq!(game.priority[v], 0);
+    }
+
+    #[test]
+    fn pgsolver_format() {
+        let lts = "des (0,1,2)\n(0,\"a\",1)".parse::<Lts>().unwrap();
+        let f = "mu X. <\"a\">X".parse::<mc::Formula>().unwrap();
+        let game = Game::new(&lts, &f, &mc::Valuation::new());
+        assert_eq!(
+            game.to_pgsolver(0),
+            "parity 4;
+start 0;
+0 1 0 2 \"0 ⊨ mu X. <a>X\";
+1 1 0 3 \"1 ⊨ mu X. <a>X\";
+2 0 0 4 \"0 ⊨ <a>X\";
+3 1 0 3 \"1 ⊨ <a>X\";
+4 0 0 1 \"1 ⊨ X\";
+"
+        );
+    }
+
+    #[test]
+    fn local() {
+        let lts =
+            "des (0,2,3)\n(1,\"a\",0)\n(1,\"a\",2)".parse::<Lts>().unwrap();
+        let f = "nu X. [a]X".parse::<mc::Formula>().unwrap();
+        let valuation = mc::Valuation::new();
+        let mut game = Game::local(&f, 0);
+        while game.explore_next(&lts, &valuation) {}
+        assert_eq!(game.vertices, vec![(0, 0), (0, 1)]);
+        let mut game = Game::local(&f, 1);
+        assert!(game.explore_next(&lts, &valuation));
+        assert_eq!((game.explored, game.vertices.len()), (1, 2));
+        while game.explore_next(&lts, &valuation) {}
+        assert_eq!(game.vertices.len(), 8);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::{
+    bes::{Bes, Expr, Sign},
+    checker::{ModelChecker, Options},
+    game::Game,
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+};
+use std::collections::BTreeSet;
+
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let game = Game::new(lts, f, valuation);
+    let bes = Bes::new(&game, lts.init());
+    let mut stats = Stats::new();
+    let values = solve(&bes, &mut stats);
+    let result =
+        lts.states().iter().filter(|&s| values[game.vertex(s)]).collect();
+    (result, stats)
+}
+
+pub struct Gauss;
+
+impl ModelChecker for Gauss {
+    fn name(&self) -> &'static str {
+        "gauss"
+    }
+
+    fn description(&self) -> &'static str {
+        "Solves the boolean equation system by Gauss elimination"
+    }
+
+    fn steps(&self) -> &'static str {
+        "substitutions"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+/// Solves `bes` by Gauss elimination, returning the value of every variable.
+pub fn solve(bes: &Bes, stats: &mut Stats) -> Vec<bool> {
+    let (order, rhs) = eliminate(bes, stats);
+    let mut values = vec![false; rhs.len()];
+    for &x in &order {
+        values[x] = rhs[x].eval(&values);
+    }
+    values
+}
+
+/// Solves `bes` by Gauss elimination, but only computes the value of the
+/// variables the initial one depends on.
+pub fn solve_init(bes: &Bes, stats: &mut Stats) -> bool {
+    let (order, rhs) = eliminate(bes, stats);
+    let mut needed = vec![false; rhs.len()];
+    let mut stack = vec![bes.init];
+    needed[bes.init] = true;
+    while let Some(x) = stack.pop() {
+        let mut vars = Vec::new();
+        rhs[x].variables(&mut vars);
+        for y in vars {
+            if !needed[y] {
+                needed[y] = true;
+                stack.push(y);
+            }
+        }
+    }
+
+    let mut values = vec![false; rhs.len()];
+    for &x in order.iter().filter(|&&x| needed[x]) {
+        values[x] = rhs[x].eval(&values);
+    }
+    values[bes.init]
+}
+
+/// Eliminates the equations of `bes` from last to first. Afterwards, the
+/// right-hand side of every variable only refers to variables before it in
+/// the returned order. Every substitution counts as a step.
+fn eliminate(bes: &Bes, stats: &mut Stats) -> (Vec<usize>, Vec<Expr>) {
+    let n = bes.vars.len();
+    let mut order = Vec::new();
+    let mut signs = vec![Sign::Nu; n];
+    let mut rhs = vec![Expr::False; n];
+    for block in &bes.blocks {
+        for eq in &block.equations {
+            order.push(eq.var);
+            signs[eq.var] = block.sign;
+            rhs[eq.var] = eq.rhs.clone();
+        }
+    }
+    let mut position = vec![0; n];
+    for (i, &x) in order.iter().enumerate() {
+        position[x] = i;
+    }
+
+    // The equations whose right-hand side may refer to a variable.
+    let mut users = vec![BTreeSet::new(); n];
+    for &x in &order {
+        let mut vars = Vec::new();
+        rhs[x].variables(&mut vars);
+        for y in vars {
+            users[y].insert(x);
+        }
+    }
+
+    for &x in order.iter().rev() {
+        // A variable that refers to itself is resolved to the extremal
+        // solution of its sign.
+        let solution = match signs[x] {
+            Sign::Mu => Expr::False,
+            Sign::Nu => Expr::True,
+        };
+        rhs[x] = rhs[x].substitute(x, &solution);
+
+        let mut vars = Vec::new();
+        rhs[x].variables(&mut vars);
+        let earlier = users[x]
+            .iter()
+            .filter(|&&y| position[y] < position[x])
+            .cloned()
+            .collect::<Vec<_>>();
+        for y in earlier {
+            rhs[y] = rhs[y].substitute(x, &rhs[x]);
+            for &z in &vars {
+                users[z].insert(y);
+            }
+            stats.steps += 1;
+        }
+    }
+    (order, rhs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elimination() {
+        let lts =
+            "des (0,2,2)\n(0,\"a\",1)\n(1,\"a\",1)".parse::<Lts>().unwrap();
+        let f = "nu X. mu Y. (<a>X || [a]Y)".parse::<mc::Formula>().unwrap();
+        let game = Game::new(&lts, &f, &mc::Valuation::new());
+        let bes = Bes::new(&game, lts.init());
+        let mut stats = Stats::new();
+        let (order, rhs) = eliminate(&bes, &mut stats);
+        assert_eq!(order.len(), game.vertices.len());
+        for (i, &x) in order.iter().enumerate() {
+            let mut vars = Vec::new();
+            rhs[x].variables(&mut vars);
+            assert!(vars.iter().all(|y| order[..i].contains(y)));
+        }
+        assert!(solve(&bes, &mut stats)[game.vertex(0)]);
+        assert!(solve_init(&bes, &mut stats));
+    }
+}
+
+generate_tests!();
@@ -1,111 +1,468 @@
 use crate::{
+    checker::{ModelChecker, Options},
     lts::{self, Lts},
-    mu_calculus as mc,
+    modal, mu_calculus as mc,
+    stats::Stats,
 };
 use std::{
-    collections::{BTreeSet, HashMap},
-    sync::atomic::Ordering,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
 };
 
-pub fn eval(lts: &Lts, f: &mc::Formula) -> BTreeSet<lts::State> {
-    let mut env = HashMap::new();
+/// The state shared by the evaluation of all subformulas. The operands of a
+/// connective are evaluated in parallel; they bind different variables and
+/// have different subformulas, so they never touch the same entries.
+struct Memo {
+    /// The free variables of every subformula, keyed by its address.
+    free: HashMap<usize, Vec<mc::VarName>>,
+    entries: Mutex<Entries>,
+}
+
+/// The values of the variables, and the last results of subformulas together
+/// with the versions of their free variables at the time. A subformula whose
+/// free variables have not been assigned since is not evaluated again;
+/// closed subformulas are thus evaluated only once.
+struct Entries {
+    env: mc::Valuation,
+    /// How often every variable has been assigned.
+    versions: HashMap<mc::VarName, u32>,
+    evaluated: HashMap<usize, Vec<u32>>,
+    results: HashMap<usize, lts::StateSet>,
+    stats: Stats,
+}
+
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    eval_with(lts, f, valuation, true)
+}
+
+pub struct EmersonLei;
+
+impl ModelChecker for EmersonLei {
+    fn name(&self) -> &'static str {
+        "emerson-lei"
+    }
+
+    fn description(&self) -> &'static str {
+        "The Emerson-Lei algorithm, which only restarts fixpoints after alternation"
+    }
+
+    fn steps(&self) -> &'static str {
+        "fixpoint iterations"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        match options.delta {
+            true => eval(lts, f, options.valuation),
+            false => eval_with(lts, f, options.valuation, false),
+        }
+    }
+}
+
+/// Evaluates `f` with the Emerson-Lei algorithm. With `delta`, iterations
+/// of fixpoints only redo the work for states whose membership may have
+/// changed since the previous iteration.
+pub fn eval_with(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+    delta: bool,
+) -> (lts::StateSet, Stats) {
+    // Resetting fixpoints relies on their signs, which negations would flip,
+    // and variables are looked up by name, so shadowing binders are renamed.
+    let f = &Arc::new(f.to_pnf().rename_bound());
+    let mut env = valuation.clone();
     for g in f.subformulas() {
         match g {
             mc::Formula::Mu { var, .. } => {
-                env.insert(*var, BTreeSet::new());
+                env.insert(var.clone(), lts::StateSet::new());
             },
             mc::Formula::Nu { var, .. } => {
-                env.insert(*var, lts.states().clone());
+                env.insert(var.clone(), lts.states().clone());
             },
             _ => (),
         }
     }
-    eval_inner(lts, f, None, &mut env)
+    let modalities = modal::Cache::new();
+    let memo = Memo::new(f, env);
+    let result = if delta {
+        eval_delta(lts, f, None, &modalities, &memo);
+        memo.lock().results.remove(&key(f)).unwrap()
+    } else {
+        eval_inner(lts, f, None, &modalities, &memo)
+    };
+    (result, memo.entries.into_inner().unwrap().stats)
 }
 
 fn eval_inner(
     lts: &Lts,
     f: &mc::Formula,
     prev_fixpoint: Option<&mc::Formula>,
-    env: &mut HashMap<mc::VarName, BTreeSet<lts::State>>,
-) -> BTreeSet<lts::State> {
+    modalities: &modal::Cache,
+    memo: &Memo,
+) -> lts::StateSet {
     use mc::Formula::*;
+    if memo.is_current(f) {
+        let mut entries = memo.lock();
+        entries.stats.cache_hits += 1;
+        return entries.results[&key(f)].clone();
+    }
+    let start = Instant::now();
+    let both = |f1, f2| {
+        rayon::join(
+            || eval_inner(lts, f1, prev_fixpoint, modalities, memo),
+            || eval_inner(lts, f2, prev_fixpoint, modalities, memo),
+        )
+    };
 
-    match f {
-        Var { name } => env[&name].clone(),
+    let result = match f {
+        Var { name } => memo.lock().env[name].clone(),
         True => lts.states().clone(),
-        False => BTreeSet::new(),
-        And { f1, f2 } => eval_inner(lts, f1, prev_fixpoint, env)
-            .intersection(&eval_inner(lts, f2, prev_fixpoint, env))
-            .cloned()
-            .collect(),
-        Or { f1, f2 } => eval_inner(lts, f1, prev_fixpoint, env)
-            .union(&eval_inner(lts, f2, prev_fixpoint, env))
-            .cloned()
-            .collect(),
+        False => lts::StateSet::new(),
+        Not { f: g } =>
+            lts.states() - &eval_inner(lts, g, prev_fixpoint, modalities, memo),
+        And { f1, f2 } => {
+            let (sat1, sat2) = both(f1, f2);
+            &sat1 & &sat2
+        },
+        Or { f1, f2 } => {
+            let (sat1, sat2) = both(f1, f2);
+            &sat1 | &sat2
+        },
+        Implies { .. } | Iff { .. } =>
+            unreachable!("formula is not in positive normal form"),
         Diamond { step, f: g } => {
-            let sat = eval_inner(lts, g, prev_fixpoint, env);
-            lts.step_transitions(step)
-                .filter(|(_s, ts)| ts.iter().any(|t| sat.contains(t)))
-                .map(|(s, _ts)| s)
-                .collect()
+            let sat = eval_inner(lts, g, prev_fixpoint, modalities, memo);
+            memo.lock().stats.modality();
+            modal::possibly(lts, step, &sat, modalities)
         },
         Box { step, f: g } => {
-            let sat = eval_inner(lts, g, prev_fixpoint, env);
-            lts.step_transitions(step)
-                .filter(|(_s, ts)| ts.iter().all(|t| sat.contains(t)))
-                .map(|(s, _ts)| s)
-                .collect()
+            let sat = eval_inner(lts, g, prev_fixpoint, modalities, memo);
+            memo.lock().stats.modality();
+            modal::necessarily(lts, step, &sat, modalities)
         },
         Mu { var, f: g } => {
             if let Some(Nu { .. }) = prev_fixpoint {
-                reset_fixpoints(lts, f, env);
+                reset_fixpoints(lts, f, memo);
             }
             loop {
-                super::ITERATIONS.fetch_add(1, Ordering::SeqCst);
-                let new = eval_inner(lts, g, Some(f), env);
-                let prev = env.insert(*var, new).unwrap();
-                if prev == env[var] {
-                    break prev;
+                memo.lock().stats.iteration(var);
+                let new = eval_inner(lts, g, Some(f), modalities, memo);
+                let mut entries = memo.lock();
+                if new == entries.env[var] {
+                    break new;
                 }
+                entries.assign(var, new);
             }
         },
         Nu { var, f: g } => {
             if let Some(Mu { .. }) = prev_fixpoint {
-                reset_fixpoints(lts, f, env);
+                reset_fixpoints(lts, f, memo);
             }
             loop {
-                super::ITERATIONS.fetch_add(1, Ordering::SeqCst);
-                let new = eval_inner(lts, g, Some(f), env);
-                let prev = env.insert(*var, new).unwrap();
-                if prev == env[var] {
-                    break prev;
+                memo.lock().stats.iteration(var);
+                let new = eval_inner(lts, g, Some(f), modalities, memo);
+                let mut entries = memo.lock();
+                if new == entries.env[var] {
+                    break new;
                 }
+                entries.assign(var, new);
             }
         },
-    }
+    };
+    memo.evaluated(f);
+    let mut entries = memo.lock();
+    entries.stats.evaluated(f, result.len(), start.elapsed());
+    entries.results.insert(key(f), result.clone());
+    result
+}
+
+/// How `eval_delta` changed the result of a subformula.
+enum Update {
+    Unchanged,
+    Replaced(lts::StateSet),
+    /// The previous result was updated in place; holds the states that
+    /// entered or left it.
+    Patched(lts::StateSet),
 }
 
-fn reset_fixpoints(
+/// Evaluates `f` like `eval_inner`, but skips subformulas none of whose
+/// operands changed since they were last evaluated. The result is stored in
+/// `memo`; the states whose membership changed are returned.
+fn eval_delta(
     lts: &Lts,
     f: &mc::Formula,
-    env: &mut HashMap<mc::VarName, BTreeSet<lts::State>>,
-) {
+    prev_fixpoint: Option<&mc::Formula>,
+    modalities: &modal::Cache,
+    memo: &Memo,
+) -> lts::StateSet {
     use mc::Formula::*;
-    match f {
-        Mu { .. } => f.subformulas().for_each(|g| match g {
-            Mu { var, .. } if g.is_open() => {
-                env.insert(*var, BTreeSet::new());
-            },
-            _ => (),
-        }),
-        Nu { .. } => f.subformulas().for_each(|g| match g {
-            Nu { var, .. } if g.is_open() => {
-                env.insert(*var, lts.states().clone());
-            },
-            _ => (),
-        }),
-        _ => panic!("Cannot reset non-fixpoint operators."),
+    use Update::*;
+    if memo.is_current(f) {
+        memo.lock().stats.cache_hits += 1;
+        return lts::StateSet::new();
+    }
+    let start = Instant::now();
+    let mut prev = memo.lock().results.remove(&key(f));
+    let seen = prev.is_some();
+
+    let new = match f {
+        Var { name } => Replaced(memo.lock().env[name].clone()),
+        True => Replaced(lts.states().clone()),
+        False => Replaced(lts::StateSet::new()),
+        Not { f: g } => Replaced(
+            lts.states() - &eval_inner(lts, g, prev_fixpoint, modalities, memo),
+        ),
+        And { f1, f2 } | Or { f1, f2 } => {
+            let (c1, c2) = rayon::join(
+                || eval_delta(lts, f1, prev_fixpoint, modalities, memo),
+                || eval_delta(lts, f2, prev_fixpoint, modalities, memo),
+            );
+            if seen && c1.is_empty() && c2.is_empty() {
+                Unchanged
+            } else {
+                let entries = memo.lock();
+                let r1 = &entries.results[&key(f1)];
+                let r2 = &entries.results[&key(f2)];
+                match f {
+                    And { .. } => Replaced(r1 & r2),
+                    _ => Replaced(r1 | r2),
+                }
+            }
+        },
+        Implies { .. } | Iff { .. } =>
+            unreachable!("formula is not in positive normal form"),
+        Diamond { step, f: g } | Box { step, f: g } => {
+            let c = eval_delta(lts, g, prev_fixpoint, modalities, memo);
+            if seen && c.is_empty() {
+                Unchanged
+            } else {
+                // Only `f` evaluates `g`, so its result can be taken out
+                // while the modality is evaluated.
+                let sat = {
+                    let mut entries = memo.lock();
+                    entries.stats.modality();
+                    entries.results.remove(&key(g)).unwrap()
+                };
+                let diamond = matches!(f, Diamond { .. });
+                // The modalities only look at the states that entered or
+                // left `sat` since their previous evaluation.
+                let new = match prev.as_mut() {
+                    Some(prev) => Patched(modal::update(
+                        lts, step, diamond, &sat, &c, prev, modalities,
+                    )),
+                    None if diamond =>
+                        Replaced(modal::possibly(lts, step, &sat, modalities)),
+                    None => Replaced(modal::necessarily(
+                        lts, step, &sat, modalities,
+                    )),
+                };
+                memo.lock().results.insert(key(g), sat);
+                new
+            }
+        },
+        Mu { var, f: g } | Nu { var, f: g } => {
+            match (f, prev_fixpoint) {
+                (Mu { .. }, Some(Nu { .. })) | (Nu { .. }, Some(Mu { .. })) =>
+                    reset_fixpoints(lts, f, memo),
+                _ => (),
+            }
+            loop {
+                memo.lock().stats.iteration(var);
+                eval_delta(lts, g, Some(f), modalities, memo);
+                let mut entries = memo.lock();
+                let body = entries.results[&key(g)].clone();
+                if body == entries.env[var] {
+                    break Replaced(body);
+                }
+                entries.assign(var, body);
+            }
+        },
+    };
+
+    memo.evaluated(f);
+    let mut entries = memo.lock();
+    let (result, changed) = match new {
+        Unchanged => (prev.unwrap(), lts::StateSet::new()),
+        Replaced(new) => {
+            let changed = match prev {
+                Some(prev) => &prev ^ &new,
+                None => lts.states() | &new,
+            };
+            (new, changed)
+        },
+        Patched(changed) => (prev.unwrap(), changed),
+    };
+    entries.stats.evaluated(f, result.len(), start.elapsed());
+    entries.results.insert(key(f), result);
+    changed
+}
+
+fn reset_fixpoints(lts: &Lts, f: &mc::Formula, memo: &Memo) {
+    let mut entries = memo.lock();
+    for var in f.open_fixpoints() {
+        let value = match f.is_mu() {
+            true => lts::StateSet::new(),
+            false => lts.states().clone(),
+        };
+        entries.assign(var, value);
+    }
+}
+
+/// Subformulas are identified by their address.
+fn key(f: &mc::Formula) -> usize {
+    f as *const _ as usize
+}
+
+impl Memo {
+    fn new(f: &Arc<mc::Formula>, env: mc::Valuation) -> Memo {
+        let free = f
+            .subformulas()
+            .map(|g| (key(g), g.free_variables().into_iter().collect()))
+            .collect();
+        let entries = Entries {
+            env,
+            versions: HashMap::new(),
+            evaluated: HashMap::new(),
+            results: HashMap::new(),
+            stats: Stats::with_subformulas(f.clone()),
+        };
+        Memo { free, entries: Mutex::new(entries) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries> {
+        self.entries.lock().unwrap()
+    }
+
+    fn snapshot(&self, entries: &Entries, f: &mc::Formula) -> Vec<u32> {
+        self.free[&key(f)]
+            .iter()
+            .map(|x| entries.versions.get(x).cloned().unwrap_or(0))
+            .collect()
+    }
+
+    /// Whether the last result of `f` is still its value.
+    fn is_current(&self, f: &mc::Formula) -> bool {
+        let entries = self.lock();
+        match entries.evaluated.get(&key(f)) {
+            Some(versions) => *versions == self.snapshot(&entries, f),
+            None => false,
+        }
+    }
+
+    /// Records that the result of `f` is up to date.
+    fn evaluated(&self, f: &mc::Formula) {
+        let mut entries = self.lock();
+        let versions = self.snapshot(&entries, f);
+        entries.evaluated.insert(key(f), versions);
+    }
+}
+
+impl Entries {
+    fn assign(&mut self, var: &mc::VarName, value: lts::StateSet) {
+        *self.versions.entry(var.clone()).or_insert(0) += 1;
+        self.env.insert(var.clone(), value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let lts =
+            "des (0,2,2)\n(0,\"a\",1)\n(1,\"a\",0)".parse::<Lts>().unwrap();
+        let f = "nu X. (<a>X && mu Y. <a>Y)".parse::<mc::Formula>().unwrap();
+        for &delta in &[true, false] {
+            let (result, stats) =
+                eval_with(&lts, &f, &mc::Valuation::new(), delta);
+            assert!(result.is_empty());
+            assert_eq!(stats.iterations["X"], 2);
+            assert_eq!(stats.iterations["Y"], 1);
+            assert_eq!(stats.steps, 3);
+            // The closed least fixpoint is only evaluated once.
+            assert_eq!(stats.cache_hits, 1);
+            let mu = stats
+                .subformulas()
+                .unwrap()
+                .into_iter()
+                .find(|g| g.formula.starts_with("mu Y"))
+                .unwrap();
+            assert_eq!((mu.evaluations, mu.states), (1, 0));
+        }
+    }
+
+    #[test]
+    fn closed_subformulas() {
+        let lts = "des (0,4,4)\n(0,\"a\",1)\n(1,\"a\",2)\n(2,\"a\",3)\n\
+                   (3,\"b\",3)"
+            .parse::<Lts>()
+            .unwrap();
+        let f = "nu X. (<a>X && mu Y. (<b>true || <a>Y))"
+            .parse::<mc::Formula>()
+            .unwrap();
+        for &delta in &[true, false] {
+            let (result, stats) =
+                eval_with(&lts, &f, &mc::Valuation::new(), delta);
+            assert!(result.is_empty());
+            assert_eq!(stats.iterations["X"], 5);
+            assert_eq!(stats.iterations["Y"], 5);
+            // `mu Y` is reused in the last four iterations of `X`, and
+            // `<b>true` in the last four iterations of `Y`.
+            assert_eq!(stats.cache_hits, 8);
+            let closed = stats
+                .subformulas()
+                .unwrap()
+                .into_iter()
+                .filter(|g| {
+                    g.formula.starts_with("mu Y") || g.formula == "<b>true"
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(closed.len(), 2);
+            assert!(closed.iter().all(|g| g.evaluations == 1));
+        }
+    }
+
+    /// Checks that fixpoints are still correctly computed from scratch.
+    mod full {
+        use super::*;
+
+        fn eval(
+            lts: &Lts,
+            f: &mc::Formula,
+            valuation: &mc::Valuation,
+        ) -> (lts::StateSet, Stats) {
+            eval_with(lts, f, valuation, false)
+        }
+
+        generate_tests!();
+    }
+
+    /// Checks that the results do not depend on the number of threads.
+    mod parallel {
+        use super::*;
+
+        fn eval(
+            lts: &Lts,
+            f: &mc::Formula,
+            valuation: &mc::Valuation,
+        ) -> (lts::StateSet, Stats) {
+            let pool =
+                rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+            pool.install(|| super::super::eval(lts, f, valuation))
+        }
+
+        generate_tests!();
     }
 }
 
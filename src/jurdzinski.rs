@@ -0,0 +1,161 @@
+use crate::{
+    checker::{ModelChecker, Options},
+    game::{Game, Player},
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+};
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let game = Game::new(lts, f, valuation);
+    let mut stats = Stats::new();
+    let even = solve(&game, &mut stats);
+    let result =
+        lts.states().iter().filter(|&s| even[game.vertex(s)]).collect();
+    (result, stats)
+}
+
+pub struct SmallProgressMeasures;
+
+impl ModelChecker for SmallProgressMeasures {
+    fn name(&self) -> &'static str {
+        "small-progress-measures"
+    }
+
+    fn description(&self) -> &'static str {
+        "Solves the parity game with Jurdziński's small progress measures"
+    }
+
+    fn steps(&self) -> &'static str {
+        "lifts"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+/// A progress measure of a vertex: for every odd priority, from the highest
+/// down, how often `Odd` may still visit it without a higher priority in
+/// between, or `Top` if `Odd` can visit it more often than there are
+/// vertices with that priority.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Measure {
+    Finite(Vec<u32>),
+    Top,
+}
+
+/// Solves a parity game with Jurdziński's small progress measures, returning
+/// for every vertex whether `Even` wins it. All measures start at zero and
+/// vertices are lifted to the least measure their successors allow until
+/// nothing changes; `Odd` wins the vertices whose measure is `Top`.
+pub fn solve(game: &Game, stats: &mut Stats) -> Vec<bool> {
+    let n = game.vertices.len();
+    let mut odd = game
+        .priority
+        .iter()
+        .cloned()
+        .filter(|&p| Player::of_priority(p) == Player::Odd)
+        .collect::<Vec<_>>();
+    odd.sort_by(|p, q| q.cmp(p));
+    odd.dedup();
+    let bounds = odd
+        .iter()
+        .map(|&p| game.priority.iter().filter(|&&q| q == p).count() as u32)
+        .collect::<Vec<_>>();
+
+    let mut measure = vec![Measure::Finite(vec![0; odd.len()]); n];
+    let mut queued = vec![true; n];
+    let mut worklist = (0..n).collect::<Vec<_>>();
+    while let Some(v) = worklist.pop() {
+        queued[v] = false;
+        let p = game.priority[v];
+        let progress = game.succ[v]
+            .iter()
+            .map(|&w| progress(&measure[w], p, &odd, &bounds));
+        let lifted = match game.owner[v] {
+            Player::Even => progress.min(),
+            Player::Odd => progress.max(),
+        }
+        .unwrap();
+        if lifted <= measure[v] {
+            continue;
+        }
+        stats.steps += 1;
+        measure[v] = lifted;
+        for &u in &game.pred[v] {
+            if !queued[u] {
+                queued[u] = true;
+                worklist.push(u);
+            }
+        }
+    }
+    measure.into_iter().map(|m| m != Measure::Top).collect()
+}
+
+/// The least measure of a vertex with priority `p` that moves to a vertex
+/// with measure `m`: the components for priorities below `p` are reset, and
+/// an odd `p` increments its own component.
+fn progress(m: &Measure, p: u32, odd: &[u32], bounds: &[u32]) -> Measure {
+    let mut next = match m {
+        Measure::Finite(m) => m.clone(),
+        Measure::Top => return Measure::Top,
+    };
+    let kept = odd.iter().take_while(|&&q| q >= p).count();
+    for c in &mut next[kept..] {
+        *c = 0;
+    }
+    if Player::of_priority(p) == Player::Even {
+        return Measure::Finite(next);
+    }
+    for i in (0..kept).rev() {
+        if next[i] < bounds[i] {
+            next[i] += 1;
+            return Measure::Finite(next);
+        }
+        next[i] = 0;
+    }
+    Measure::Top
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress() {
+        let odd = [5, 3, 1];
+        let bounds = [1, 2, 1];
+        let m = |m: &[u32]| Measure::Finite(m.to_vec());
+        assert_eq!(
+            super::progress(&m(&[0, 1, 1]), 2, &odd, &bounds),
+            m(&[0, 1, 0])
+        );
+        assert_eq!(
+            super::progress(&m(&[0, 1, 1]), 3, &odd, &bounds),
+            m(&[0, 2, 0])
+        );
+        assert_eq!(
+            super::progress(&m(&[0, 2, 1]), 3, &odd, &bounds),
+            m(&[1, 0, 0])
+        );
+        assert_eq!(
+            super::progress(&m(&[1, 2, 0]), 3, &odd, &bounds),
+            Measure::Top
+        );
+        assert_eq!(
+            super::progress(&m(&[1, 2, 0]), 6, &odd, &bounds),
+            m(&[0, 0, 0])
+        );
+    }
+}
+
+generate_tests!();
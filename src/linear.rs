@@ -0,0 +1,209 @@
+use crate::{
+    checker::{ModelChecker, Options},
+    game::{Game, Player},
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+    MyuError,
+};
+/// Evaluates an alternation-free formula in time linear in the size of its
+/// game.
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let game = Game::new(lts, f, valuation);
+    let mut stats = Stats::new();
+    let even = solve(&game, &mut stats);
+    let result =
+        lts.states().iter().filter(|&s| even[game.vertex(s)]).collect();
+    (result, stats)
+}
+
+pub struct Linear;
+
+impl ModelChecker for Linear {
+    fn name(&self) -> &'static str {
+        "linear"
+    }
+
+    fn description(&self) -> &'static str {
+        "Solves the parity game of an alternation-free formula in linear time"
+    }
+
+    fn steps(&self) -> &'static str {
+        "strongly connected components"
+    }
+
+    fn supports(&self, f: &mc::Formula) -> Result<(), MyuError> {
+        // Lowering regular modalities may introduce alternation.
+        match f.to_pnf().lower_regular().rename_bound().dependent_ad() {
+            0 | 1 => Ok(()),
+            dad => Err(MyuError::AlternationError(dad)),
+        }
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+/// Solves the game of an alternation-free formula, returning for every
+/// vertex whether `Even` wins it. Strongly connected components are solved
+/// from the bottom up; a component only passes through fixpoints of one
+/// sign, so its vertices start out at the extremal solution of that sign and
+/// are flipped by counting their successors that have not been flipped yet.
+pub fn solve(game: &Game, stats: &mut Stats) -> Vec<bool> {
+    let n = game.vertices.len();
+    let mut even = vec![false; n];
+    let mut component = vec![None; n];
+    let mut count = vec![0; n];
+    let mut worklist = Vec::new();
+
+    for (c, vertices) in components(&game.succ).into_iter().enumerate() {
+        for &v in &vertices {
+            component[v] = Some(c);
+        }
+        let top = vertices.iter().map(|&v| game.priority[v]).max().unwrap();
+        let initial = Player::of_priority(top) == Player::Even;
+        // The owner of a vertex that flips as soon as one successor does.
+        let eager = match initial {
+            true => Player::Odd,
+            false => Player::Even,
+        };
+
+        for &v in &vertices {
+            even[v] = initial;
+        }
+        for &v in &vertices {
+            count[v] =
+                game.succ[v].iter().filter(|&&w| even[w] == initial).count();
+        }
+        for &v in &vertices {
+            let flips = match game.owner[v] == eager {
+                true => count[v] < game.succ[v].len(),
+                false => count[v] == 0,
+            };
+            if flips {
+                even[v] = !initial;
+                worklist.push(v);
+            }
+        }
+        while let Some(w) = worklist.pop() {
+            for &v in &game.pred[w] {
+                if component[v] != Some(c) || even[v] != initial {
+                    continue;
+                }
+                count[v] -= 1;
+                if game.owner[v] == eager || count[v] == 0 {
+                    even[v] = !initial;
+                    worklist.push(v);
+                }
+            }
+        }
+        stats.steps += 1;
+    }
+    even
+}
+
+/// The strongly connected components of the graph with successors `succ`,
+/// found by Tarjan's algorithm. Every component comes after the components
+/// reachable from it.
+pub fn components(succ: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let n = succ.len();
+    // Vertices are numbered from 1 in the order of their visit, so 0 marks
+    // the ones not visited yet.
+    let mut index = vec![0; n];
+    let mut low = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next = 1;
+
+    for root in 0..n {
+        if index[root] != 0 {
+            continue;
+        }
+        // The vertices being visited, with the next successor to look at.
+        let mut calls = vec![(root, 0)];
+        index[root] = next;
+        low[root] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some(&(v, i)) = calls.last() {
+            if let Some(&w) = succ[v].get(i) {
+                calls.last_mut().unwrap().1 += 1;
+                if index[w] == 0 {
+                    index[w] = next;
+                    low[w] = next;
+                    next += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+
+            calls.pop();
+            if let Some(&(u, _)) = calls.last() {
+                low[u] = low[u].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let mut component = Vec::new();
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::improved;
+
+    /// Falls back to the Emerson-Lei algorithm for formulas with
+    /// alternation, like `main` does.
+    fn eval(
+        lts: &Lts,
+        f: &mc::Formula,
+        valuation: &mc::Valuation,
+    ) -> (lts::StateSet, Stats) {
+        let lowered = f.to_pnf().lower_regular().rename_bound();
+        match lowered.dependent_ad() {
+            0 | 1 => super::eval(lts, f, valuation),
+            _ => improved::eval(lts, f, valuation),
+        }
+    }
+
+    #[test]
+    fn components() {
+        let lts =
+            "des (0,2,2)\n(0,\"a\",1)\n(1,\"a\",0)".parse::<Lts>().unwrap();
+        let f = "nu X. <a>X".parse::<mc::Formula>().unwrap();
+        let game = Game::new(&lts, &f, &mc::Valuation::new());
+        let components = super::components(&game.succ);
+        assert_eq!(components.len(), 1);
+        assert_eq!(components[0].len(), game.vertices.len());
+    }
+
+    generate_tests!();
+}
@@ -0,0 +1,107 @@
+use crate::{
+    bes::Bes,
+    checker::{ModelChecker, Options},
+    game::Game,
+    gauss,
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+};
+
+/// The number of vertices explored before the first attempt to decide the
+/// initial state.
+const ROUND: usize = 64;
+
+/// Decides whether the initial state satisfies `f`, only exploring the pairs
+/// `(state, subformula)` reachable from the initial state and `f`. The game
+/// is explored in rounds that double the number of explored vertices; after
+/// every round, the system of the explored part is solved with the
+/// unexplored variables fixed to false and to true. Exploration stops as
+/// soon as both agree on the initial state. The result contains at most the
+/// initial state.
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let mut game = Game::local(f, lts.init());
+    let mut stats = Stats::new();
+    let mut round = ROUND;
+    let holds = loop {
+        let mut done = false;
+        while game.explored < round && !done {
+            done = !game.explore_next(lts, valuation);
+        }
+        let lower = Bes::partial(&game, lts.init(), false);
+        if gauss::solve_init(&lower, &mut stats) {
+            break true;
+        }
+        if done {
+            break false;
+        }
+        let upper = Bes::partial(&game, lts.init(), true);
+        if !gauss::solve_init(&upper, &mut stats) {
+            break false;
+        }
+        round *= 2;
+    };
+    let result = match holds {
+        true => std::iter::once(lts.init()).collect(),
+        false => lts::StateSet::new(),
+    };
+    (result, stats)
+}
+
+pub struct Local;
+
+impl ModelChecker for Local {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn description(&self) -> &'static str {
+        "Explores the boolean equation system from the initial state until it is decided"
+    }
+
+    fn steps(&self) -> &'static str {
+        "substitutions"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_early() {
+        let n = 100_000;
+        let mut aut = format!("des (0,{},{})\n", n, n + 1);
+        for s in 0..n {
+            aut.push_str(&format!("({},\"a\",{})\n", s, s + 1));
+        }
+        let lts = aut.parse::<Lts>().unwrap();
+        let valuation = mc::Valuation::new();
+        // The greatest fixpoint spans the whole chain, but the first operand
+        // decides the disjunction after the first round.
+        let f = "<a>true || nu X. [a]X".parse::<mc::Formula>().unwrap();
+        let (result, stats) = eval(&lts, &f, &valuation);
+        assert!(result.contains(&0));
+        // Eliminating the whole system would take a step per state.
+        assert!(stats.steps <= 2 * ROUND as u64);
+        let f = "[a]false && nu X. [a]X".parse::<mc::Formula>().unwrap();
+        let (result, stats) = eval(&lts, &f, &valuation);
+        assert!(result.is_empty());
+        assert!(stats.steps <= 2 * ROUND as u64);
+    }
+}
+
+generate_tests!();
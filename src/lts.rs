@@ -1,3 +1,7 @@
+mod state_set;
+
+pub use state_set::StateSet;
+
 use crate::MyuError;
 use combine::{
     between, eof, from_str,
@@ -9,50 +13,148 @@ use combine::{
     stream::position,
     EasyParser, Parser,
 };
-use std::{
-    collections::{BTreeSet, HashMap},
-    str::FromStr,
-};
+use std::{collections::HashMap, str::FromStr};
 
 pub type State = u32;
 pub type Label = String;
+/// The index of an interned label.
+pub type LabelId = usize;
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Lts {
     init: State,
-    states: BTreeSet<State>,
-    trans: HashMap<(State, Label), Vec<State>>,
+    states: StateSet,
+    /// The labels in increasing order; transitions refer to them by index.
+    labels: Vec<Label>,
+    /// The transitions of every label, grouped by source state.
+    forward: Vec<Csr>,
+    /// The transitions of every label, grouped by target state.
+    backward: Vec<Csr>,
+}
+
+/// A relation on states in compressed sparse row form.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Csr {
+    /// The states related to some state, in increasing order.
+    rows: Vec<State>,
+    /// The states related to `rows[i]` are `columns[offsets[i]..offsets[i +
+    /// 1]]`.
+    offsets: Vec<usize>,
+    columns: Vec<State>,
 }
 
 impl Lts {
-    pub fn states(&self) -> &BTreeSet<State> {
+    /// Builds an LTS from its initial state and transitions.
+    pub fn new<'a>(
+        init: State,
+        edges: impl IntoIterator<Item = (State, &'a str, State)>,
+    ) -> Lts {
+        let edges = edges.into_iter().collect::<Vec<_>>();
+        let mut labels = edges.iter().map(|&(_, l, _)| l).collect::<Vec<_>>();
+        labels.sort_unstable();
+        labels.dedup();
+        let ids = labels
+            .iter()
+            .enumerate()
+            .map(|(id, &l)| (l, id))
+            .collect::<HashMap<_, _>>();
+
+        let mut states = StateSet::new();
+        states.insert(init);
+        let mut pairs = vec![Vec::new(); labels.len()];
+        for &(start, label, end) in &edges {
+            states.insert(start);
+            states.insert(end);
+            pairs[ids[label]].push((start, end));
+        }
+        drop(edges);
+
+        // Both directions are built from the same pairs, sorted by target
+        // for the backward relation and then by source for the forward one.
+        let mut forward = Vec::with_capacity(pairs.len());
+        let mut backward = Vec::with_capacity(pairs.len());
+        for mut p in pairs {
+            p.sort_unstable_by_key(|&(s, t)| (t, s));
+            p.dedup();
+            backward.push(Csr::from_sorted(p.iter().map(|&(s, t)| (t, s))));
+            p.sort_unstable();
+            forward.push(Csr::from_sorted(p.into_iter()));
+        }
+        Lts {
+            init,
+            states,
+            labels: labels.into_iter().map(str::to_owned).collect(),
+            forward,
+            backward,
+        }
+    }
+
+    pub fn states(&self) -> &StateSet {
         &self.states
     }
 
-    pub fn step_transitions<'a>(
+    /// The labels that satisfy `step`.
+    pub fn labels_matching(&self, step: impl Fn(&str) -> bool) -> Vec<LabelId> {
+        (0..self.labels.len()).filter(|&l| step(&self.labels[l])).collect()
+    }
+
+    /// Yields the transitions labeled `l`, as pairs of a state and the
+    /// targets of its transitions.
+    pub fn transitions(
+        &self,
+        l: LabelId,
+    ) -> impl Iterator<Item = (State, &[State])> {
+        self.forward[l].rows()
+    }
+
+    /// The sources of the transitions labeled `l` that end in `t`.
+    pub fn predecessors(&self, l: LabelId, t: State) -> &[State] {
+        self.backward[l].row(t)
+    }
+
+    /// Yields the outgoing transitions of `s` as pairs of label and target.
+    pub fn successors<'a>(
         &'a self,
-        step: &'a str,
-    ) -> impl Iterator<Item = (State, Vec<State>)> + 'a {
-        self.states().iter().cloned().map(move |s| {
-            self.trans
-                .get(&(s, step.to_owned()))
-                .cloned()
-                .map(|ts| (s, ts))
-                .unwrap_or((s, vec![]))
-        })
+        s: State,
+    ) -> impl Iterator<Item = (&'a Label, State)> + 'a {
+        self.labels
+            .iter()
+            .zip(&self.forward)
+            .flat_map(move |(l, csr)| csr.row(s).iter().map(move |&t| (l, t)))
     }
 
     pub fn init(&self) -> State {
         self.init
     }
+}
+
+impl Csr {
+    /// Builds the relation from its pairs, which must be in increasing order
+    /// and without duplicates.
+    fn from_sorted(pairs: impl Iterator<Item = (State, State)>) -> Csr {
+        let mut csr = Csr::default();
+        for (s, t) in pairs {
+            if csr.rows.last() != Some(&s) {
+                csr.rows.push(s);
+                csr.offsets.push(csr.columns.len());
+            }
+            csr.columns.push(t);
+        }
+        csr.offsets.push(csr.columns.len());
+        csr
+    }
+
+    fn rows(&self) -> impl Iterator<Item = (State, &[State])> {
+        self.rows.iter().enumerate().map(move |(i, &s)| {
+            (s, &self.columns[self.offsets[i]..self.offsets[i + 1]])
+        })
+    }
 
-    fn add_edge(&mut self, start: State, label: &str, end: State) {
-        self.states.insert(start);
-        self.states.insert(end);
-        self.trans
-            .entry((start, label.to_owned()))
-            .or_insert_with(Vec::new)
-            .push(end);
+    fn row(&self, s: State) -> &[State] {
+        match self.rows.binary_search(&s) {
+            Ok(i) => &self.columns[self.offsets[i]..self.offsets[i + 1]],
+            Err(_) => &[],
+        }
     }
 }
 
@@ -60,7 +162,7 @@ impl FromStr for Lts {
     type Err = MyuError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let int = || from_str(take_while1(|c: char| c.is_digit(10)));
+        let int = || from_str(take_while1(|c: char| c.is_ascii_digit()));
         let non_newline_spaces = || skip_many(char(' ').or(char('\t')));
         let aut_header = || {
             (
@@ -86,12 +188,10 @@ impl FromStr for Lts {
             )
         };
 
-        let mut lts = Lts::default();
         let ((_, initial, n_transitions, _n_states), mut s) = aut_header()
             .easy_parse(position::Stream::new(s))
             .map_err(|e| MyuError::LtsParseError(e.to_string()))?;
-        lts.init = initial;
-        lts.trans.reserve(n_transitions as usize);
+        let mut edges = Vec::with_capacity(n_transitions as usize);
 
         while let Ok((_, mut rest)) =
             non_newline_spaces().and(newline()).skip(spaces()).easy_parse(s)
@@ -103,11 +203,11 @@ impl FromStr for Lts {
             let ((start, label, end), rest) = aut_edge()
                 .easy_parse(rest)
                 .map_err(|e| MyuError::LtsParseError(e.to_string()))?;
-            lts.add_edge(start, label, end);
+            edges.push((start, label, end));
             s = rest;
         }
 
-        Ok(lts)
+        Ok(Lts::new(initial, edges))
     }
 }
 
@@ -131,19 +231,23 @@ mod tests {
 (8,"free(p2, f1)",0)
 (9,"free(p1, f2)",0)"#;
 
-        let mut expected = Lts::default();
-        expected.add_edge(0, "lock(p2, f2)", 1);
-        expected.add_edge(0, "lock(p1, f1)", 2);
-        expected.add_edge(1, "lock(p1, f1)", 3);
-        expected.add_edge(1, "lock(p2, f1)", 4);
-        expected.add_edge(2, "lock(p2, f2)", 3);
-        expected.add_edge(2, "lock(p1, f2)", 5);
-        expected.add_edge(4, "eat(p2)", 6);
-        expected.add_edge(5, "eat(p1)", 7);
-        expected.add_edge(6, "free(p2, f2)", 8);
-        expected.add_edge(7, "free(p1, f1)", 9);
-        expected.add_edge(8, "free(p2, f1)", 0);
-        expected.add_edge(9, "free(p1, f2)", 0);
+        let expected = Lts::new(
+            0,
+            vec![
+                (0, "lock(p2, f2)", 1),
+                (0, "lock(p1, f1)", 2),
+                (1, "lock(p1, f1)", 3),
+                (1, "lock(p2, f1)", 4),
+                (2, "lock(p2, f2)", 3),
+                (2, "lock(p1, f2)", 5),
+                (4, "eat(p2)", 6),
+                (5, "eat(p1)", 7),
+                (6, "free(p2, f2)", 8),
+                (7, "free(p1, f1)", 9),
+                (8, "free(p2, f1)", 0),
+                (9, "free(p1, f2)", 0),
+            ],
+        );
 
         assert_eq!(input.parse::<Lts>(), Ok(expected));
 
@@ -162,21 +266,53 @@ mod tests {
 (9,\"i\",0)
 ";
 
-        let mut expected = Lts::default();
-        expected.add_edge(0, "i", 1);
-        expected.add_edge(0, "i", 2);
-        expected.add_edge(1, "i", 3);
-        expected.add_edge(1, "i", 4);
-        expected.add_edge(2, "i", 5);
-        expected.add_edge(2, "i", 4);
-        expected.add_edge(3, "others", 6);
-        expected.add_edge(5, "plato", 7);
-        expected.add_edge(6, "i", 8);
-        expected.add_edge(7, "i", 9);
-        expected.add_edge(8, "i", 0);
-        expected.add_edge(9, "i", 0);
+        let expected = Lts::new(
+            0,
+            vec![
+                (0, "i", 1),
+                (0, "i", 2),
+                (1, "i", 3),
+                (1, "i", 4),
+                (2, "i", 5),
+                (2, "i", 4),
+                (3, "others", 6),
+                (5, "plato", 7),
+                (6, "i", 8),
+                (7, "i", 9),
+                (8, "i", 0),
+                (9, "i", 0),
+            ],
+        );
 
         let result = input.parse::<Lts>();
         assert_eq!(result, Ok(expected));
     }
+
+    #[test]
+    fn transitions() {
+        let lts = Lts::new(
+            3,
+            vec![
+                (0, "b", 1),
+                (0, "a", 2),
+                (0, "a", 1),
+                (2, "a", 0),
+                (0, "a", 2),
+            ],
+        );
+        assert_eq!(lts.states().iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(
+            lts.successors(0).collect::<Vec<_>>(),
+            [(&"a".to_owned(), 1), (&"a".to_owned(), 2), (&"b".to_owned(), 1)]
+        );
+        assert_eq!(lts.successors(1).count(), 0);
+        let a = lts.labels_matching(|l| l == "a");
+        assert_eq!(a, [0]);
+        assert_eq!(
+            lts.transitions(a[0]).collect::<Vec<_>>(),
+            [(0, &[1, 2][..]), (2, &[0][..])]
+        );
+        assert_eq!(lts.predecessors(a[0], 2), [0]);
+        assert!(lts.predecessors(a[0], 3).is_empty());
+    }
 }
@@ -0,0 +1,218 @@
+use super::State;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FromIterator,
+    ops::{BitAnd, BitOr, BitXor, Sub},
+};
+
+const BITS: usize = 64;
+
+/// A set of states, stored as a dense bitvector indexed by state. Set
+/// operations work on 64 states at a time.
+#[derive(Clone, Default)]
+pub struct StateSet {
+    words: Vec<u64>,
+}
+
+impl StateSet {
+    pub fn new() -> StateSet {
+        StateSet::default()
+    }
+
+    pub fn contains(&self, s: &State) -> bool {
+        let (word, bit) = position(*s);
+        match self.words.get(word) {
+            Some(w) => w & bit != 0,
+            None => false,
+        }
+    }
+
+    /// Adds `s`, returning whether it was not yet present.
+    pub fn insert(&mut self, s: State) -> bool {
+        let (word, bit) = position(s);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let absent = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        absent
+    }
+
+    /// Removes `s`, returning whether it was present.
+    pub fn remove(&mut self, s: &State) -> bool {
+        let (word, bit) = position(*s);
+        match self.words.get_mut(word) {
+            Some(w) if *w & bit != 0 => {
+                *w &= !bit;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Yields the states in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = State> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &w)| {
+            let mut w = w;
+            std::iter::from_fn(move || match w {
+                0 => None,
+                _ => {
+                    let bit = w.trailing_zeros() as usize;
+                    w &= w - 1;
+                    Some((i * BITS + bit) as State)
+                },
+            })
+        })
+    }
+
+    pub fn is_subset(&self, other: &StateSet) -> bool {
+        self.words.iter().enumerate().all(|(i, &w)| w & !other.word(i) == 0)
+    }
+
+    pub fn union_with(&mut self, other: &StateSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (w, &v) in self.words.iter_mut().zip(&other.words) {
+            *w |= v;
+        }
+    }
+
+    pub fn intersect_with(&mut self, other: &StateSet) {
+        for (i, w) in self.words.iter_mut().enumerate() {
+            *w &= other.word(i);
+        }
+    }
+
+    pub fn difference_with(&mut self, other: &StateSet) {
+        for (w, &v) in self.words.iter_mut().zip(&other.words) {
+            *w &= !v;
+        }
+    }
+
+    fn word(&self, i: usize) -> u64 {
+        self.words.get(i).cloned().unwrap_or(0)
+    }
+
+    /// The words up to the last non-zero one, which determine the set.
+    fn significant(&self) -> &[u64] {
+        let len = self.words.iter().rposition(|&w| w != 0).map_or(0, |i| i + 1);
+        &self.words[..len]
+    }
+}
+
+fn position(s: State) -> (usize, u64) {
+    let s = s as usize;
+    (s / BITS, 1 << (s % BITS))
+}
+
+impl PartialEq for StateSet {
+    fn eq(&self, other: &StateSet) -> bool {
+        self.significant() == other.significant()
+    }
+}
+
+impl Eq for StateSet {}
+
+impl Hash for StateSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.significant().hash(state)
+    }
+}
+
+impl fmt::Debug for StateSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl FromIterator<State> for StateSet {
+    fn from_iter<I: IntoIterator<Item = State>>(iter: I) -> StateSet {
+        let mut set = StateSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<State> for StateSet {
+    fn extend<I: IntoIterator<Item = State>>(&mut self, iter: I) {
+        for s in iter {
+            self.insert(s);
+        }
+    }
+}
+
+impl BitOr for &StateSet {
+    type Output = StateSet;
+
+    fn bitor(self, other: &StateSet) -> StateSet {
+        let mut set = self.clone();
+        set.union_with(other);
+        set
+    }
+}
+
+impl BitAnd for &StateSet {
+    type Output = StateSet;
+
+    fn bitand(self, other: &StateSet) -> StateSet {
+        let mut set = self.clone();
+        set.intersect_with(other);
+        set
+    }
+}
+
+impl BitXor for &StateSet {
+    type Output = StateSet;
+
+    fn bitxor(self, other: &StateSet) -> StateSet {
+        let mut set = self | other;
+        set.difference_with(&(self & other));
+        set
+    }
+}
+
+impl Sub for &StateSet {
+    type Output = StateSet;
+
+    fn sub(self, other: &StateSet) -> StateSet {
+        let mut set = self.clone();
+        set.difference_with(other);
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations() {
+        let a = [0, 3, 64, 200].iter().cloned().collect::<StateSet>();
+        let b = [3, 65, 200].iter().cloned().collect::<StateSet>();
+        assert_eq!((&a | &b).iter().collect::<Vec<_>>(), [0, 3, 64, 65, 200]);
+        assert_eq!((&a & &b).iter().collect::<Vec<_>>(), [3, 200]);
+        assert_eq!((&a - &b).iter().collect::<Vec<_>>(), [0, 64]);
+        assert_eq!((&a ^ &b).iter().collect::<Vec<_>>(), [0, 64, 65]);
+        assert_eq!(a.len(), 4);
+        assert!((&a & &b).is_subset(&b));
+        assert!(!a.is_subset(&b));
+
+        // Sets are equal regardless of how far their storage extends.
+        let mut c = [3].iter().cloned().collect::<StateSet>();
+        c.insert(300);
+        assert!(c.remove(&300));
+        assert_eq!(c, [3].iter().cloned().collect());
+        assert_eq!(&b - &b, StateSet::new());
+        assert!((&b - &b).is_empty());
+    }
+}
@@ -1,36 +1,108 @@
 #[macro_use]
 mod tests;
+mod bdd;
+mod bes;
+mod checker;
+mod game;
+mod gauss;
 mod improved;
+mod jurdzinski;
+mod linear;
+mod local;
 mod lts;
+mod modal;
 mod mu_calculus;
 mod naive;
+mod stats;
+mod strategy_improvement;
+mod symbolic;
+mod tableau;
+mod witness;
+mod zielonka;
 
 use crate::{lts::Lts, mu_calculus as mc};
 use ansi_term::Colour;
 use anyhow::Context;
 use atty::Stream;
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{self, Read, Write},
     path::PathBuf,
-    sync::atomic::{AtomicU32, Ordering},
 };
 use structopt::{clap::AppSettings, StructOpt};
 use thiserror::Error;
 
-static ITERATIONS: AtomicU32 = AtomicU32::new(0);
-
 /// A model-checker for Labeled Transition Systems using a subset of the modal μ-calculus.
 #[derive(StructOpt)]
 #[structopt(global_settings(&[AppSettings::ColoredHelp]))]
 struct Args {
     /// File specifying the LTS to be verified in aldebaran format
-    lts: PathBuf,
+    #[structopt(required_unless = "list-algorithms")]
+    lts: Option<PathBuf>,
     /// File specifying the formula to check in modal μ-calculus.
-    mcf: PathBuf,
-    /// Use naive algorithm instead of the Emerson-Lei algorithm
-    #[structopt(long)]
+    #[structopt(required_unless = "list-algorithms")]
+    mcf: Option<PathBuf>,
+    /// Use naive algorithm instead of the Emerson-Lei algorithm, shorthand
+    /// for `--algorithm naive`
+    #[structopt(long, conflicts_with = "algorithm")]
     naive: bool,
+    /// The algorithm used for checking, see --list-algorithms; by default,
+    /// the linear-time algorithm if ƒ is alternation-free and the Emerson-Lei
+    /// algorithm otherwise
+    #[structopt(long, value_name = "name", parse(try_from_str = checker::find))]
+    algorithm: Option<&'static dyn checker::ModelChecker>,
+    /// List the available algorithms and exit
+    #[structopt(long)]
+    list_algorithms: bool,
+    /// Recompute fixpoints from scratch in every iteration of the
+    /// Emerson-Lei algorithm, instead of only the states affected by the
+    /// previous iteration
+    #[structopt(long)]
+    no_delta: bool,
+    /// The number of threads used by the naive and Emerson-Lei algorithms,
+    /// or 0 for one per core
+    #[structopt(long, value_name = "N", default_value = "1")]
+    threads: usize,
+    /// Assign a valuation to a free variable of the formula, given as a
+    /// comma-separated list of states, `all` or `none`
+    #[structopt(
+        long,
+        value_name = "X=states",
+        number_of_values = 1,
+        parse(try_from_str = parse_binding)
+    )]
+    bind: Vec<(mc::VarName, Binding)>,
+    /// Explain the verdict for the initial state with a witness or a
+    /// counterexample
+    #[structopt(
+        long,
+        value_name = "format",
+        possible_values = &["text", "json", "dot"]
+    )]
+    witness: Option<witness::Format>,
+    /// Write the witness to a file instead of stdout; required for the
+    /// `json` and `dot` formats, so that they are not mixed with the verdict
+    #[structopt(
+        long,
+        value_name = "file",
+        requires = "witness",
+        required_ifs = &[("witness", "json"), ("witness", "dot")]
+    )]
+    witness_file: Option<PathBuf>,
+    /// Print the statistics of the run to stderr: iterations per fixpoint,
+    /// modality evaluations, and the evaluations, set sizes and wall time of
+    /// every subformula
+    #[structopt(long, value_name = "format", possible_values = &["text", "json"])]
+    stats: Option<stats::Format>,
+    /// Write the boolean equation system of the formula in mCRL2 format
+    /// (`.bes`) or its parity game in PGSolver format (`.gm`) to a file
+    #[structopt(long, value_name = "file", number_of_values = 1)]
+    emit_bes: Vec<PathBuf>,
+}
+
+enum Binding {
+    All,
+    States(lts::StateSet),
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -39,44 +111,162 @@ pub enum MyuError {
     McfParseError(String),
     #[error("failed to parse labeled transition system: {0}")]
     LtsParseError(String),
+    #[error("variable {0} occurs under an odd number of negations")]
+    PositivityError(mc::VarName),
+    #[error("ƒ has free variables: {}", list_occurrences(.0))]
+    OpenFormulaError(Vec<(mc::VarName, mc::Position)>),
+    #[error("the linear-time algorithm requires an alternation-free formula, but dAD(ƒ) = {0} after rewriting regular modalities into fixpoints")]
+    AlternationError(u16),
+}
+
+fn list_occurrences(occurrences: &[(mc::VarName, mc::Position)]) -> String {
+    occurrences
+        .iter()
+        .map(|(name, pos)| format!("{} (at {})", name, pos))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_binding(s: &str) -> Result<(mc::VarName, Binding), String> {
+    let mut parts = s.splitn(2, '=');
+    let (var, states) = match (parts.next(), parts.next()) {
+        (Some(var), Some(states)) => (var.trim(), states.trim()),
+        _ => return Err(format!("expected X=states, found {:?}", s)),
+    };
+    let binding = match states {
+        "all" => Binding::All,
+        "none" => Binding::States(lts::StateSet::new()),
+        _ => Binding::States(
+            states
+                .split(',')
+                .map(|state| state.trim().parse::<lts::State>())
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("invalid state in {:?}: {}", s, e))?,
+        ),
+    };
+    Ok((var.to_owned(), binding))
 }
 
 fn run() -> anyhow::Result<()> {
     let args = Args::from_args();
-    let mut lts_file = File::open(&args.lts)
-        .with_context(|| format!("failed to open {:#?}", &args.lts))?;
+    if args.list_algorithms {
+        for checker in checker::ALGORITHMS {
+            writeln!(
+                io::stdout(),
+                "{:<25}{}",
+                checker.name(),
+                checker.description()
+            )?;
+        }
+        return Ok(());
+    }
+    // Both are required unless the algorithms are listed.
+    let (lts_path, mcf_path) = (args.lts.unwrap(), args.mcf.unwrap());
+
+    let mut lts_file = File::open(&lts_path)
+        .with_context(|| format!("failed to open {:#?}", &lts_path))?;
     let mut lts = String::new();
     lts_file
         .read_to_string(&mut lts)
-        .with_context(|| format!("failed to read from {:#?}", &args.lts))?;
+        .with_context(|| format!("failed to read from {:#?}", &lts_path))?;
 
-    let mut mcf_file = File::open(&args.mcf)
-        .with_context(|| format!("failed to open {:#?}", &args.mcf))?;
+    let mut mcf_file = File::open(&mcf_path)
+        .with_context(|| format!("failed to open {:#?}", &mcf_path))?;
     let mut mcf_str = String::new();
     mcf_file
         .read_to_string(&mut mcf_str)
-        .with_context(|| format!("failed to read from {:#?}", &args.mcf))?;
+        .with_context(|| format!("failed to read from {:#?}", &mcf_path))?;
 
     let lts = lts.parse::<Lts>()?;
-    let mcf =
-        mcf_str.parse::<mc::Formula>().map_err(MyuError::McfParseError)?;
+    let parsed =
+        mcf_str.parse::<mc::Parsed>().map_err(MyuError::McfParseError)?;
+    parsed.formula.check_positive()?;
+
+    let mut valuation = mc::Valuation::new();
+    for (var, binding) in &args.bind {
+        let states = match binding {
+            Binding::All => lts.states().clone(),
+            Binding::States(states) => {
+                if let Some(s) = (states - lts.states()).iter().next() {
+                    anyhow::bail!(
+                        "state {} bound to {} does not exist",
+                        s,
+                        var
+                    );
+                }
+                states.clone()
+            },
+        };
+        valuation.insert(var.clone(), states);
+    }
+    parsed.check_closed(&valuation.keys().cloned().collect::<Vec<_>>())?;
+    let mcf = parsed.formula;
 
-    writeln!(io::stdout(), "Begin checking {:?}...", &args.mcf)?;
+    writeln!(io::stdout(), "Begin checking {:?}...", &mcf_path)?;
     writeln!(io::stdout(), "Let ƒ ≔ {}", mcf)?;
 
+    if !args.emit_bes.is_empty() {
+        let game = game::Game::new(&lts, &mcf, &valuation);
+        for path in &args.emit_bes {
+            let output = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("bes") => bes::Bes::new(&game, lts.init()).to_string(),
+                Some("gm") => game.to_pgsolver(lts.init()),
+                _ => anyhow::bail!(
+                    "cannot determine the format of {:#?}, expected a .bes or \
+                     .gm file",
+                    path
+                ),
+            };
+            fs::write(path, output)
+                .with_context(|| format!("failed to write to {:#?}", path))?;
+            writeln!(io::stdout(), "Written {:?}", path)?;
+        }
+    }
+
+    let pnf = mcf.to_pnf().rename_bound();
+    // Lowering regular modalities may introduce alternation, as in
+    // `nu X. <a*>X`.
+    let dad = pnf.lower_regular().rename_bound().dependent_ad();
+    let (checker, reason): (&dyn checker::ModelChecker, _) =
+        match (args.naive, args.algorithm) {
+            (true, _) => (&naive::Naive, "chosen with --naive"),
+            (false, Some(checker)) => (checker, "chosen with --algorithm"),
+            (false, None) if dad <= 1 =>
+                (&linear::Linear, "ƒ is alternation-free"),
+            (false, None) if pnf.dependent_ad() <= 1 => (
+                &improved::EmersonLei,
+                "regular modalities introduce alternation",
+            ),
+            (false, None) =>
+                (&improved::EmersonLei, "ƒ is not alternation-free"),
+        };
     writeln!(
         io::stdout(),
-        "ND(ƒ) = {}    AD(ƒ) = {}    dAD(ƒ) = {}",
-        mcf.nesting_depth(),
-        mcf.alternation_depth(),
-        mcf.dependent_ad()
+        "ND(ƒ) = {}    AD(ƒ) = {}    dAD(ƒ) = {}    using {} ({})",
+        pnf.nesting_depth(),
+        pnf.alternation_depth(),
+        dad,
+        checker.name(),
+        reason
     )?;
 
-    let result = if args.naive {
-        naive::eval(&lts, &mcf)
-    } else {
-        improved::eval(&lts, &mcf)
-    };
+    let options =
+        checker::Options { valuation: &valuation, delta: !args.no_delta };
+    let pool =
+        rayon::ThreadPoolBuilder::new().num_threads(args.threads).build()?;
+    // The witness is built in the same pool, since it evaluates subformulas
+    // in parallel as well.
+    let explain = args.witness.is_some();
+    let (outcome, witness) = pool.install(|| {
+        let outcome = checker.check(&lts, &mcf, &options)?;
+        let holds = outcome.result.contains(&lts.init());
+        let witness = match explain {
+            true => Some(checker.witness(&lts, &mcf, &options, holds)),
+            false => None,
+        };
+        Ok::<_, MyuError>((outcome, witness))
+    })?;
+    let result = outcome.result;
 
     write!(io::stdout(), "ƒ = {{")?;
     let mut first = true;
@@ -94,11 +284,21 @@ fn run() -> anyhow::Result<()> {
 
     writeln!(
         io::stdout(),
-        "Checking required {} fixpoint iterations",
-        ITERATIONS.load(Ordering::SeqCst)
+        "Checking required {} {} in {:.3?}",
+        outcome.stats.steps,
+        checker.steps(),
+        outcome.stats.time
     )?;
+    if outcome.stats.cache_hits > 0 {
+        writeln!(
+            io::stdout(),
+            "Reused cached results of subformulas {} times",
+            outcome.stats.cache_hits
+        )?;
+    }
 
-    if result.contains(&lts.init()) {
+    let holds = result.contains(&lts.init());
+    if holds {
         print_fancy(
             &format!("Verdict: state {} satisfies ƒ", lts.init()),
             Colour::Green,
@@ -110,6 +310,43 @@ fn run() -> anyhow::Result<()> {
         )?;
     }
 
+    if let (Some(format), Some(witness)) = (args.witness, witness) {
+        let mut output = witness.render(format);
+        if format == witness::Format::Json {
+            output.push('\n');
+        }
+        match &args.witness_file {
+            Some(path) => {
+                fs::write(path, output).with_context(|| {
+                    format!("failed to write to {:#?}", path)
+                })?;
+                writeln!(io::stdout(), "Written {:?}", path)?;
+            },
+            None => {
+                writeln!(
+                    io::stdout(),
+                    "{} for state {} ⊨ {}:",
+                    if holds { "Witness" } else { "Counterexample" },
+                    lts.init(),
+                    witness.formula
+                )?;
+                write!(io::stdout(), "{}", output)?;
+            },
+        }
+    }
+
+    // The statistics go to stderr, so that they can be read without the
+    // verdict.
+    if let Some(format) = args.stats {
+        if format == stats::Format::Text {
+            writeln!(io::stderr(), "Statistics:")?;
+        }
+        write!(io::stderr(), "{}", outcome.stats.render(format))?;
+        if format == stats::Format::Json {
+            writeln!(io::stderr())?;
+        }
+    }
+
     Ok(())
 }
 
@@ -0,0 +1,336 @@
+use crate::{
+    lts::{self, Lts},
+    mu_calculus::{ActionFormula, RegFormula},
+};
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+/// The previous evaluations of every action modality. A modality that is
+/// evaluated again, e.g. in the next iteration of a fixpoint, is updated by
+/// the states that entered or left its argument since. Modalities can be
+/// evaluated from several threads, as long as every modality is evaluated by
+/// one thread at a time.
+#[derive(Default)]
+pub struct Cache {
+    /// Keyed by the address of the action formula.
+    modalities: Mutex<HashMap<(usize, bool), Counters>>,
+}
+
+/// The evaluation of `<act>f` or `[act]f` for the last argument `sat`.
+struct Counters {
+    labels: Vec<lts::LabelId>,
+    sat: lts::StateSet,
+    result: lts::StateSet,
+    /// For every state, the number of its `act`-successors in `sat` for a
+    /// diamond, or outside `sat` for a box.
+    count: Vec<AtomicU32>,
+}
+
+/// Computes the states that can take a `step`-path to a state in `sat`, i.e.
+/// the semantics of `<step>f` given the semantics `sat` of `f`.
+pub fn possibly(
+    lts: &Lts,
+    step: &RegFormula,
+    sat: &lts::StateSet,
+    cache: &Cache,
+) -> lts::StateSet {
+    use RegFormula::*;
+
+    match step {
+        Action { act } => cache.evaluate(lts, act, true, sat),
+        Seq { r1, r2 } =>
+            possibly(lts, r1, &possibly(lts, r2, sat, cache), cache),
+        Choice { r1, r2 } =>
+            &possibly(lts, r1, sat, cache) | &possibly(lts, r2, sat, cache),
+        Star { r } => possibly_star(lts, r, sat, cache),
+        Plus { r } =>
+            possibly(lts, r, &possibly_star(lts, r, sat, cache), cache),
+    }
+}
+
+fn possibly_star(
+    lts: &Lts,
+    r: &RegFormula,
+    sat: &lts::StateSet,
+    cache: &Cache,
+) -> lts::StateSet {
+    let mut reach = sat.clone();
+    loop {
+        let new = possibly(lts, r, &reach, cache);
+        if new.is_subset(&reach) {
+            break reach;
+        }
+        reach.union_with(&new);
+    }
+}
+
+/// Computes the states of which all `step`-paths end in a state in `sat`,
+/// i.e. the semantics of `[step]f` given the semantics `sat` of `f`.
+pub fn necessarily(
+    lts: &Lts,
+    step: &RegFormula,
+    sat: &lts::StateSet,
+    cache: &Cache,
+) -> lts::StateSet {
+    use RegFormula::*;
+
+    match step {
+        Action { act } => cache.evaluate(lts, act, false, sat),
+        Seq { r1, r2 } =>
+            necessarily(lts, r1, &necessarily(lts, r2, sat, cache), cache),
+        Choice { r1, r2 } =>
+            &necessarily(lts, r1, sat, cache)
+                & &necessarily(lts, r2, sat, cache),
+        Star { r } => necessarily_star(lts, r, sat, cache),
+        Plus { r } =>
+            necessarily(lts, r, &necessarily_star(lts, r, sat, cache), cache),
+    }
+}
+
+fn necessarily_star(
+    lts: &Lts,
+    r: &RegFormula,
+    sat: &lts::StateSet,
+    cache: &Cache,
+) -> lts::StateSet {
+    let mut safe = sat.clone();
+    loop {
+        let new = necessarily(lts, r, &safe, cache);
+        if safe.is_subset(&new) {
+            break safe;
+        }
+        safe.intersect_with(&new);
+    }
+}
+
+/// Updates `result`, the previous semantics of `<step>f` (if `diamond`) or
+/// `[step]f` computed with `cache`, to the new semantics `sat` of `f`, given
+/// the states `changed` that entered or left `sat` since. Returns the states
+/// that entered or left `result`.
+pub fn update(
+    lts: &Lts,
+    step: &RegFormula,
+    diamond: bool,
+    sat: &lts::StateSet,
+    changed: &lts::StateSet,
+    result: &mut lts::StateSet,
+    cache: &Cache,
+) -> lts::StateSet {
+    let flipped = match step {
+        RegFormula::Action { act } => cache
+            .with(lts, act, diamond, |c| c.update(lts, diamond, sat, changed)),
+        // The arguments of the inner modalities are not known in advance.
+        _ => match diamond {
+            true => &*result ^ &possibly(lts, step, sat, cache),
+            false => &*result ^ &necessarily(lts, step, sat, cache),
+        },
+    };
+    for s in flipped.iter() {
+        if !result.remove(&s) {
+            result.insert(s);
+        }
+    }
+    flipped
+}
+
+impl Cache {
+    pub fn new() -> Cache {
+        Cache::default()
+    }
+
+    /// Evaluates `<act>` (if `diamond`) or `[act]` on `sat`, only visiting
+    /// the predecessors of states that entered or left `sat` since the last
+    /// evaluation.
+    fn evaluate(
+        &self,
+        lts: &Lts,
+        act: &ActionFormula,
+        diamond: bool,
+        sat: &lts::StateSet,
+    ) -> lts::StateSet {
+        self.with(lts, act, diamond, |c| {
+            c.update(lts, diamond, sat, &(sat ^ &c.sat));
+            c.result.clone()
+        })
+    }
+
+    /// Runs `update` on the counters of `<act>` or `[act]`, which no other
+    /// thread uses in the meantime.
+    fn with<T>(
+        &self,
+        lts: &Lts,
+        act: &ActionFormula,
+        diamond: bool,
+        update: impl FnOnce(&mut Counters) -> T,
+    ) -> T {
+        let key = (act as *const _ as usize, diamond);
+        let entry = self.modalities.lock().unwrap().remove(&key);
+        let mut counters =
+            entry.unwrap_or_else(|| Counters::new(lts, act, diamond));
+        let result = update(&mut counters);
+        self.modalities.lock().unwrap().insert(key, counters);
+        result
+    }
+}
+
+impl Counters {
+    /// The evaluation of the modality for an empty argument.
+    fn new(lts: &Lts, act: &ActionFormula, diamond: bool) -> Counters {
+        let labels = lts.labels_matching(|label| act.matches(label));
+        let bound = lts.states().iter().last().map_or(0, |s| s as usize + 1);
+        let mut count = vec![0; bound];
+        let mut result = lts::StateSet::new();
+        if !diamond {
+            for &l in &labels {
+                for (s, ts) in lts.transitions(l) {
+                    count[s as usize] += ts.len() as u32;
+                }
+            }
+            result = lts
+                .states()
+                .iter()
+                .filter(|&s| count[s as usize] == 0)
+                .collect();
+        }
+        let count = count.into_iter().map(AtomicU32::new).collect();
+        Counters { labels, sat: lts::StateSet::new(), result, count }
+    }
+
+    /// Adjusts the counts of the predecessors of the states that entered or
+    /// left `sat`, split over the threads, and then updates the membership
+    /// of the predecessors whose count changed. The states in `changed` may
+    /// have entered or left `sat`; all others must be unchanged. Returns the
+    /// states that entered or left the result.
+    fn update(
+        &mut self,
+        lts: &Lts,
+        diamond: bool,
+        sat: &lts::StateSet,
+        changed: &lts::StateSet,
+    ) -> lts::StateSet {
+        let mut entered_or_left = Vec::new();
+        for t in changed.iter() {
+            let entering = sat.contains(&t);
+            let moved = match entering {
+                true => self.sat.insert(t),
+                false => self.sat.remove(&t),
+            };
+            if moved {
+                entered_or_left.push((t, entering));
+            }
+        }
+        let (labels, count) = (&self.labels, &self.count);
+
+        let touched = entered_or_left
+            .par_iter()
+            .fold(Vec::new, |mut touched, &(t, entering)| {
+                for &l in labels {
+                    for &s in lts.predecessors(l, t) {
+                        let count = &count[s as usize];
+                        if entering == diamond {
+                            count.fetch_add(1, Ordering::Relaxed);
+                        } else {
+                            count.fetch_sub(1, Ordering::Relaxed);
+                        }
+                        touched.push(s);
+                    }
+                }
+                touched
+            })
+            .collect::<Vec<_>>();
+
+        let mut flipped = lts::StateSet::new();
+        for s in touched.into_iter().flatten() {
+            let count = count[s as usize].load(Ordering::Relaxed);
+            let holds = match diamond {
+                true => count > 0,
+                false => count == 0,
+            };
+            let moved = match holds {
+                true => self.result.insert(s),
+                false => self.result.remove(&s),
+            };
+            if moved {
+                flipped.insert(s);
+            }
+        }
+        flipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mu_calculus as mc;
+
+    #[test]
+    fn incremental() {
+        let lts = "des (0,5,4)\n(0,\"a\",1)\n(0,\"a\",2)\n(1,\"b\",2)\n\
+                   (2,\"a\",3)\n(3,\"a\",3)"
+            .parse::<Lts>()
+            .unwrap();
+        let step = match "<a*>true".parse::<mc::Formula>().unwrap() {
+            mc::Formula::Diamond { step, .. } => step,
+            _ => unreachable!(),
+        };
+        let set = |states: &[lts::State]| states.iter().cloned().collect();
+
+        // Evaluating the same modalities on changing arguments gives the
+        // same results as evaluating them from scratch.
+        let cache = Cache::new();
+        for sat in &[set(&[2]), set(&[1, 3]), set(&[]), set(&[0, 1, 2, 3])] {
+            let expected = |diamond| {
+                let fresh = &Cache::new();
+                match diamond {
+                    true => possibly(&lts, &step, sat, fresh),
+                    false => necessarily(&lts, &step, sat, fresh),
+                }
+            };
+            assert_eq!(possibly(&lts, &step, sat, &cache), expected(true));
+            assert_eq!(necessarily(&lts, &step, sat, &cache), expected(false));
+        }
+
+        assert_eq!(possibly(&lts, &step, &set(&[2]), &cache), set(&[0, 2]));
+        assert_eq!(
+            necessarily(&lts, &step, &set(&[2, 3]), &cache),
+            set(&[2, 3])
+        );
+
+        // Updating with the states that changed gives the same results.
+        let act = mc::RegFormula::Action { act: ActionFormula::from("a") };
+        for &diamond in &[true, false] {
+            let cache = Cache::new();
+            let mut prev = set(&[]);
+            let mut result = match diamond {
+                true => possibly(&lts, &act, &prev, &cache),
+                false => necessarily(&lts, &act, &prev, &cache),
+            };
+            for sat in &[set(&[2]), set(&[1, 3]), set(&[0, 1, 2, 3])] {
+                let before = result.clone();
+                let flipped = update(
+                    &lts,
+                    &act,
+                    diamond,
+                    sat,
+                    &(&prev ^ sat),
+                    &mut result,
+                    &cache,
+                );
+                let fresh = &Cache::new();
+                let expected = match diamond {
+                    true => possibly(&lts, &act, sat, fresh),
+                    false => necessarily(&lts, &act, sat, fresh),
+                };
+                assert_eq!(result, expected);
+                assert_eq!(flipped, &before ^ &expected);
+                prev = sat.clone();
+            }
+        }
+    }
+}
@@ -1,22 +1,86 @@
 mod parser;
 
-use std::{collections::BTreeSet, fmt, str::FromStr};
+use crate::{lts, MyuError};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    str::FromStr,
+};
 
-pub type VarName = char;
+pub type VarName = String;
+
+/// An assignment of sets of states to recursion variables.
+pub type Valuation = HashMap<VarName, lts::StateSet>;
+
+/// A position in the source text of a formula, counting from 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A formula as parsed from its source text, which remembers where its free
+/// variables occur.
+#[derive(Clone, Debug)]
+pub struct Parsed {
+    pub formula: Formula,
+    free: Vec<(VarName, Position)>,
+}
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Formula {
     False,
     True,
     Var { name: VarName },
+    Not { f: Box<Formula> },
     And { f1: Box<Formula>, f2: Box<Formula> },
     Or { f1: Box<Formula>, f2: Box<Formula> },
-    Diamond { step: String, f: Box<Formula> },
-    Box { step: String, f: Box<Formula> },
+    Implies { f1: Box<Formula>, f2: Box<Formula> },
+    Iff { f1: Box<Formula>, f2: Box<Formula> },
+    Diamond { step: RegFormula, f: Box<Formula> },
+    Box { step: RegFormula, f: Box<Formula> },
     Mu { var: VarName, f: Box<Formula> },
     Nu { var: VarName, f: Box<Formula> },
 }
 
+/// A regular formula over action formulas, describing the sequences of steps
+/// quantified over by a modality.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegFormula {
+    Action { act: ActionFormula },
+    Seq { r1: Box<RegFormula>, r2: Box<RegFormula> },
+    Choice { r1: Box<RegFormula>, r2: Box<RegFormula> },
+    Star { r: Box<RegFormula> },
+    Plus { r: Box<RegFormula> },
+}
+
+/// A formula describing a set of action labels.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ActionFormula {
+    True,
+    False,
+    Label {
+        name: String,
+    },
+    /// Matches labels of the form `name(args)`, where an argument of `None`
+    /// matches any value.
+    Pattern {
+        name: String,
+        args: Vec<Option<String>>,
+    },
+    Not {
+        a: Box<ActionFormula>,
+    },
+    And {
+        a1: Box<ActionFormula>,
+        a2: Box<ActionFormula>,
+    },
+    Or {
+        a1: Box<ActionFormula>,
+        a2: Box<ActionFormula>,
+    },
+}
+
 #[derive(Clone, Debug)]
 pub struct Subformulas<'a> {
     children: Vec<&'a Formula>,
@@ -29,7 +93,7 @@ struct Variables {
 }
 
 impl Formula {
-    pub fn subformulas(&self) -> Subformulas {
+    pub fn subformulas(&self) -> Subformulas<'_> {
         Subformulas { children: vec![self] }
     }
 
@@ -42,9 +106,11 @@ impl Formula {
         use Formula::*;
         match self {
             True | False | Var { .. } => 0,
-            Box { f, .. } | Diamond { f, .. } => f.nesting_depth(),
-            And { f1, f2 } | Or { f1, f2 } =>
-                f1.nesting_depth().max(f2.nesting_depth()),
+            Not { f } | Box { f, .. } | Diamond { f, .. } => f.nesting_depth(),
+            And { f1, f2 }
+            | Or { f1, f2 }
+            | Implies { f1, f2 }
+            | Iff { f1, f2 } => f1.nesting_depth().max(f2.nesting_depth()),
             Mu { f, .. } | Nu { f, .. } => 1 + f.nesting_depth(),
         }
     }
@@ -53,8 +119,12 @@ impl Formula {
         use Formula::*;
         match self {
             True | False | Var { .. } => 0,
-            Box { f, .. } | Diamond { f, .. } => f.alternation_depth(),
-            And { f1, f2 } | Or { f1, f2 } =>
+            Not { f } | Box { f, .. } | Diamond { f, .. } =>
+                f.alternation_depth(),
+            And { f1, f2 }
+            | Or { f1, f2 }
+            | Implies { f1, f2 }
+            | Iff { f1, f2 } =>
                 f1.alternation_depth().max(f2.alternation_depth()),
             Mu { f, .. } => 1.max(f.alternation_depth()).max(
                 1 + f
@@ -79,13 +149,15 @@ impl Formula {
         use Formula::*;
         match self {
             True | False | Var { .. } => 0,
-            Box { f, .. } | Diamond { f, .. } => f.dependent_ad(),
-            And { f1, f2 } | Or { f1, f2 } =>
-                f1.dependent_ad().max(f2.dependent_ad()),
+            Not { f } | Box { f, .. } | Diamond { f, .. } => f.dependent_ad(),
+            And { f1, f2 }
+            | Or { f1, f2 }
+            | Implies { f1, f2 }
+            | Iff { f1, f2 } => f1.dependent_ad().max(f2.dependent_ad()),
             Mu { var, f } => 1.max(f.dependent_ad()).max(
                 1 + f
                     .subformulas()
-                    .filter(|g| g.is_nu() && g.variables().used.contains(&var))
+                    .filter(|g| g.is_nu() && g.variables().used.contains(var))
                     .map(|g| g.dependent_ad())
                     .max()
                     .unwrap_or(0),
@@ -93,7 +165,7 @@ impl Formula {
             Nu { var, f } => 1.max(f.dependent_ad()).max(
                 1 + f
                     .subformulas()
-                    .filter(|g| g.is_mu() && g.variables().used.contains(&var))
+                    .filter(|g| g.is_mu() && g.variables().used.contains(var))
                     .map(|g| g.dependent_ad())
                     .max()
                     .unwrap_or(0),
@@ -102,34 +174,316 @@ impl Formula {
     }
 
     pub fn is_mu(&self) -> bool {
-        match self {
+        matches!(self, Formula::Mu { .. })
+    }
+
+    pub fn is_nu(&self) -> bool {
+        matches!(self, Formula::Nu { .. })
+    }
+
+    /// The variables of the open fixpoints among the subformulas of the
+    /// fixpoint `self` that have the same sign. These must be restarted when
+    /// `self` is entered again from a fixpoint of the opposite sign.
+    pub fn open_fixpoints(&self) -> impl Iterator<Item = &VarName> {
+        let mu = match self {
             Formula::Mu { .. } => true,
-            _ => false,
+            Formula::Nu { .. } => false,
+            _ => panic!("Cannot reset non-fixpoint operators."),
+        };
+        self.subformulas().filter_map(move |g| match g {
+            Formula::Mu { var, .. } | Formula::Nu { var, .. }
+                if g.is_mu() == mu && g.is_open() =>
+                Some(var),
+            _ => None,
+        })
+    }
+
+    /// Checks that every bound variable occurs under an even number of
+    /// negations, measured from its binder. This guarantees that the
+    /// fixpoints of the formula exist.
+    pub fn check_positive(&self) -> Result<(), MyuError> {
+        self.check_positive_inner(false, &mut Vec::new())
+    }
+
+    /// Returns an equivalent formula in positive normal form, i.e. with
+    /// negations pushed inwards until they only apply to free variables.
+    pub fn to_pnf(&self) -> Formula {
+        match self {
+            Formula::True | Formula::False | Formula::Var { .. } =>
+                self.clone(),
+            Formula::Not { f } => f.to_pnf().dual(&mut Vec::new()),
+            Formula::And { f1, f2 } => Formula::And {
+                f1: Box::new(f1.to_pnf()),
+                f2: Box::new(f2.to_pnf()),
+            },
+            Formula::Or { f1, f2 } => Formula::Or {
+                f1: Box::new(f1.to_pnf()),
+                f2: Box::new(f2.to_pnf()),
+            },
+            Formula::Implies { f1, f2 } => Formula::Or {
+                f1: Box::new(f1.to_pnf().dual(&mut Vec::new())),
+                f2: Box::new(f2.to_pnf()),
+            },
+            Formula::Iff { f1, f2 } => {
+                let (g1, g2) = (f1.to_pnf(), f2.to_pnf());
+                Formula::And {
+                    f1: Box::new(Formula::Or {
+                        f1: Box::new(g1.dual(&mut Vec::new())),
+                        f2: Box::new(g2.clone()),
+                    }),
+                    f2: Box::new(Formula::Or {
+                        f1: Box::new(g2.dual(&mut Vec::new())),
+                        f2: Box::new(g1),
+                    }),
+                }
+            },
+            Formula::Diamond { step, f } =>
+                Formula::Diamond { step: step.clone(), f: Box::new(f.to_pnf()) },
+            Formula::Box { step, f } =>
+                Formula::Box { step: step.clone(), f: Box::new(f.to_pnf()) },
+            Formula::Mu { var, f } =>
+                Formula::Mu { var: var.clone(), f: Box::new(f.to_pnf()) },
+            Formula::Nu { var, f } =>
+                Formula::Nu { var: var.clone(), f: Box::new(f.to_pnf()) },
         }
     }
 
-    pub fn is_nu(&self) -> bool {
+    /// Returns an alpha-equivalent formula in which every fixpoint binds a
+    /// distinct variable that does not occur free in the formula. Shadowed
+    /// binders are renamed by priming their variable.
+    pub fn rename_bound(&self) -> Formula {
+        let mut taken = self.free_variables();
+        self.rename_bound_inner(&mut taken, &mut Vec::new())
+    }
+
+    /// Returns an equivalent formula in which every modality quantifies over a
+    /// single step, by expressing the regular operators as fixpoints. Fresh
+    /// variables are obtained by priming `Z`, but binders may be duplicated,
+    /// so the result should be renamed before evaluation.
+    pub fn lower_regular(&self) -> Formula {
+        let vars = self.variables();
+        let mut taken = vars.declared;
+        taken.extend(vars.used);
+        self.lower_regular_inner(&mut taken)
+    }
+
+    /// Whether the textual representation of the formula ends in the body of
+    /// a fixpoint, which would extend over any binary operator following it.
+    fn extends_right(&self) -> bool {
+        use Formula::*;
         match self {
-            Formula::Nu { .. } => true,
+            Mu { .. } | Nu { .. } => true,
+            Not { f } | Diamond { f, .. } | Box { f, .. } => f.extends_right(),
             _ => false,
         }
     }
 
+    pub fn free_variables(&self) -> BTreeSet<VarName> {
+        use Formula::*;
+        match self {
+            True | False => BTreeSet::new(),
+            Var { name } => std::iter::once(name.clone()).collect(),
+            Not { f } | Diamond { f, .. } | Box { f, .. } => f.free_variables(),
+            And { f1, f2 }
+            | Or { f1, f2 }
+            | Implies { f1, f2 }
+            | Iff { f1, f2 } => {
+                let mut vars = f1.free_variables();
+                vars.append(&mut f2.free_variables());
+                vars
+            },
+            Mu { var, f } | Nu { var, f } => {
+                let mut vars = f.free_variables();
+                vars.remove(var);
+                vars
+            },
+        }
+    }
+
+    fn rename_bound_inner(
+        &self,
+        taken: &mut BTreeSet<VarName>,
+        scope: &mut Vec<(VarName, VarName)>,
+    ) -> Formula {
+        match self {
+            Formula::True | Formula::False => self.clone(),
+            Formula::Var { name } => {
+                let name = match scope.iter().rev().find(|(old, _)| old == name)
+                {
+                    Some((_, new)) => new.clone(),
+                    None => name.clone(),
+                };
+                Formula::Var { name }
+            },
+            Formula::Not { f } =>
+                Formula::Not { f: Box::new(f.rename_bound_inner(taken, scope)) },
+            Formula::And { f1, f2 } => Formula::And {
+                f1: Box::new(f1.rename_bound_inner(taken, scope)),
+                f2: Box::new(f2.rename_bound_inner(taken, scope)),
+            },
+            Formula::Or { f1, f2 } => Formula::Or {
+                f1: Box::new(f1.rename_bound_inner(taken, scope)),
+                f2: Box::new(f2.rename_bound_inner(taken, scope)),
+            },
+            Formula::Implies { f1, f2 } => Formula::Implies {
+                f1: Box::new(f1.rename_bound_inner(taken, scope)),
+                f2: Box::new(f2.rename_bound_inner(taken, scope)),
+            },
+            Formula::Iff { f1, f2 } => Formula::Iff {
+                f1: Box::new(f1.rename_bound_inner(taken, scope)),
+                f2: Box::new(f2.rename_bound_inner(taken, scope)),
+            },
+            Formula::Diamond { step, f } => Formula::Diamond {
+                step: step.clone(),
+                f: Box::new(f.rename_bound_inner(taken, scope)),
+            },
+            Formula::Box { step, f } => Formula::Box {
+                step: step.clone(),
+                f: Box::new(f.rename_bound_inner(taken, scope)),
+            },
+            Formula::Mu { var, f } | Formula::Nu { var, f } => {
+                let mut fresh = var.clone();
+                while taken.contains(&fresh) {
+                    fresh.push('\'');
+                }
+                taken.insert(fresh.clone());
+                scope.push((var.clone(), fresh.clone()));
+                let g = Box::new(f.rename_bound_inner(taken, scope));
+                scope.pop();
+                match self {
+                    Formula::Mu { .. } => Formula::Mu { var: fresh, f: g },
+                    _ => Formula::Nu { var: fresh, f: g },
+                }
+            },
+        }
+    }
+
+    fn lower_regular_inner(&self, taken: &mut BTreeSet<VarName>) -> Formula {
+        let lower = |f: &Formula, taken: &mut BTreeSet<VarName>| {
+            Box::new(f.lower_regular_inner(taken))
+        };
+        match self {
+            Formula::True | Formula::False | Formula::Var { .. } =>
+                self.clone(),
+            Formula::Not { f } => Formula::Not { f: lower(f, taken) },
+            Formula::And { f1, f2 } =>
+                Formula::And { f1: lower(f1, taken), f2: lower(f2, taken) },
+            Formula::Or { f1, f2 } =>
+                Formula::Or { f1: lower(f1, taken), f2: lower(f2, taken) },
+            Formula::Implies { f1, f2 } =>
+                Formula::Implies { f1: lower(f1, taken), f2: lower(f2, taken) },
+            Formula::Iff { f1, f2 } =>
+                Formula::Iff { f1: lower(f1, taken), f2: lower(f2, taken) },
+            Formula::Diamond { step, f } =>
+                lower_modality(true, step, *lower(f, taken), taken),
+            Formula::Box { step, f } =>
+                lower_modality(false, step, *lower(f, taken), taken),
+            Formula::Mu { var, f } =>
+                Formula::Mu { var: var.clone(), f: lower(f, taken) },
+            Formula::Nu { var, f } =>
+                Formula::Nu { var: var.clone(), f: lower(f, taken) },
+        }
+    }
+
+    fn check_positive_inner(
+        &self,
+        negated: bool,
+        scope: &mut Vec<(VarName, bool)>,
+    ) -> Result<(), MyuError> {
+        use Formula::*;
+        match self {
+            True | False => Ok(()),
+            Var { name } => match scope.iter().rev().find(|(v, _)| v == name) {
+                Some((_, polarity)) if *polarity != negated =>
+                    Err(MyuError::PositivityError(name.clone())),
+                _ => Ok(()),
+            },
+            Not { f } => f.check_positive_inner(!negated, scope),
+            And { f1, f2 } | Or { f1, f2 } => {
+                f1.check_positive_inner(negated, scope)?;
+                f2.check_positive_inner(negated, scope)
+            },
+            Implies { f1, f2 } => {
+                f1.check_positive_inner(!negated, scope)?;
+                f2.check_positive_inner(negated, scope)
+            },
+            // Both operands of a bi-implication occur positively as well as
+            // negatively.
+            Iff { f1, f2 } => {
+                f1.check_positive_inner(negated, scope)?;
+                f1.check_positive_inner(!negated, scope)?;
+                f2.check_positive_inner(negated, scope)?;
+                f2.check_positive_inner(!negated, scope)
+            },
+            Diamond { f, .. } | Box { f, .. } =>
+                f.check_positive_inner(negated, scope),
+            Mu { var, f } | Nu { var, f } => {
+                scope.push((var.clone(), negated));
+                let result = f.check_positive_inner(negated, scope);
+                scope.pop();
+                result
+            },
+        }
+    }
+
+    /// Computes the negation of a formula in positive normal form. Variables
+    /// bound within the negated formula are substituted by their own
+    /// negation, which cancels out against the negation pushed onto them.
+    fn dual(&self, bound: &mut Vec<VarName>) -> Formula {
+        match self {
+            Formula::True => Formula::False,
+            Formula::False => Formula::True,
+            Formula::Var { name } if bound.contains(name) => self.clone(),
+            Formula::Var { .. } => Formula::Not { f: Box::new(self.clone()) },
+            Formula::Not { f } => (**f).clone(),
+            Formula::And { f1, f2 } => Formula::Or {
+                f1: Box::new(f1.dual(bound)),
+                f2: Box::new(f2.dual(bound)),
+            },
+            Formula::Or { f1, f2 } => Formula::And {
+                f1: Box::new(f1.dual(bound)),
+                f2: Box::new(f2.dual(bound)),
+            },
+            Formula::Implies { .. } | Formula::Iff { .. } =>
+                unreachable!("formula is not in positive normal form"),
+            Formula::Diamond { step, f } =>
+                Formula::Box { step: step.clone(), f: Box::new(f.dual(bound)) },
+            Formula::Box { step, f } => Formula::Diamond {
+                step: step.clone(),
+                f: Box::new(f.dual(bound)),
+            },
+            Formula::Mu { var, f } | Formula::Nu { var, f } => {
+                bound.push(var.clone());
+                let g = Box::new(f.dual(bound));
+                bound.pop();
+                match self {
+                    Formula::Mu { .. } =>
+                        Formula::Nu { var: var.clone(), f: g },
+                    _ => Formula::Mu { var: var.clone(), f: g },
+                }
+            },
+        }
+    }
+
     fn variables(&self) -> Variables {
         use Formula::*;
         let mut vars = Variables::default();
         match self {
             Var { name } => {
-                vars.used.insert(*name);
+                vars.used.insert(name.clone());
             },
-            And { f1, f2 } | Or { f1, f2 } => {
+            And { f1, f2 }
+            | Or { f1, f2 }
+            | Implies { f1, f2 }
+            | Iff { f1, f2 } => {
                 vars = f1.variables();
                 vars.union(f2.variables());
             },
-            Diamond { f, .. } | Box { f, .. } => vars = f.variables(),
+            Not { f } | Diamond { f, .. } | Box { f, .. } =>
+                vars = f.variables(),
             Mu { var, f } | Nu { var, f } => {
                 vars = f.variables();
-                vars.declared.insert(*var);
+                vars.declared.insert(var.clone());
             },
             _ => (),
         }
@@ -137,6 +491,62 @@ impl Formula {
     }
 }
 
+/// Expresses `<step>f` (or `[step]f` if `diamond` is false) using modalities
+/// over action formulas only.
+fn lower_modality(
+    diamond: bool,
+    step: &RegFormula,
+    f: Formula,
+    taken: &mut BTreeSet<VarName>,
+) -> Formula {
+    match step {
+        RegFormula::Action { .. } if diamond =>
+            Formula::Diamond { step: step.clone(), f: Box::new(f) },
+        RegFormula::Action { .. } =>
+            Formula::Box { step: step.clone(), f: Box::new(f) },
+        RegFormula::Seq { r1, r2 } => {
+            let f = lower_modality(diamond, r2, f, taken);
+            lower_modality(diamond, r1, f, taken)
+        },
+        RegFormula::Choice { r1, r2 } => {
+            let (f1, f2) = (
+                Box::new(lower_modality(diamond, r1, f.clone(), taken)),
+                Box::new(lower_modality(diamond, r2, f, taken)),
+            );
+            if diamond {
+                Formula::Or { f1, f2 }
+            } else {
+                Formula::And { f1, f2 }
+            }
+        },
+        // <R*>f = mu Z. (f || <R>Z) and [R*]f = nu Z. (f && [R]Z)
+        RegFormula::Star { r } => {
+            let mut var = String::from("Z");
+            while taken.contains(&var) {
+                var.push('\'');
+            }
+            taken.insert(var.clone());
+            let step = lower_modality(
+                diamond,
+                r,
+                Formula::Var { name: var.clone() },
+                taken,
+            );
+            let (f1, f2) = (Box::new(f), Box::new(step));
+            if diamond {
+                Formula::Mu { var, f: Box::new(Formula::Or { f1, f2 }) }
+            } else {
+                Formula::Nu { var, f: Box::new(Formula::And { f1, f2 }) }
+            }
+        },
+        RegFormula::Plus { r } => {
+            let star = RegFormula::Star { r: r.clone() };
+            let f = lower_modality(diamond, &star, f, taken);
+            lower_modality(diamond, r, f, taken)
+        },
+    }
+}
+
 impl Variables {
     fn union(&mut self, mut other: Variables) {
         self.declared.append(&mut other.declared);
@@ -144,6 +554,85 @@ impl Variables {
     }
 }
 
+impl RegFormula {
+    fn is_atomic(&self) -> bool {
+        match self {
+            RegFormula::Action { act } => act.is_atomic(),
+            _ => false,
+        }
+    }
+}
+
+impl ActionFormula {
+    pub fn matches(&self, label: &str) -> bool {
+        use ActionFormula::*;
+        match self {
+            True => true,
+            False => false,
+            Label { name } => name == label,
+            Pattern { name, args } => {
+                let (label_name, label_args) = split_label(label);
+                name == label_name
+                    && args.len() == label_args.len()
+                    && args.iter().zip(label_args).all(|(arg, label_arg)| {
+                        let term = |t: &str| {
+                            t.chars()
+                                .filter(|c| !c.is_whitespace())
+                                .collect::<String>()
+                        };
+                        match arg {
+                            Some(arg) => term(arg) == term(label_arg),
+                            None => true,
+                        }
+                    })
+            },
+            Not { a } => !a.matches(label),
+            And { a1, a2 } => a1.matches(label) && a2.matches(label),
+            Or { a1, a2 } => a1.matches(label) || a2.matches(label),
+        }
+    }
+
+    fn is_atomic(&self) -> bool {
+        !matches!(self, ActionFormula::And { .. } | ActionFormula::Or { .. })
+    }
+}
+
+/// Splits an action label `name(arg_1, ..., arg_n)` into its name and its
+/// top-level arguments.
+fn split_label(label: &str) -> (&str, Vec<&str>) {
+    let open = match label.find('(') {
+        Some(i) if label.ends_with(')') => i,
+        _ => return (label, Vec::new()),
+    };
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, open + 1);
+    for (i, c) in label.char_indices().filter(|&(i, _)| i > open) {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => args.push(label[start..i].trim()),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(label[start..i].trim());
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    (&label[..open], args)
+}
+
+impl From<&str> for RegFormula {
+    fn from(name: &str) -> RegFormula {
+        RegFormula::Action { act: ActionFormula::from(name) }
+    }
+}
+
+impl From<&str> for ActionFormula {
+    fn from(name: &str) -> ActionFormula {
+        ActionFormula::Label { name: name.to_owned() }
+    }
+}
+
 impl<'a> Iterator for Subformulas<'a> {
     type Item = &'a Formula;
 
@@ -154,6 +643,10 @@ impl<'a> Iterator for Subformulas<'a> {
             match f {
                 And { f1, f2 } => self.children.extend_from_slice(&[f1, f2]),
                 Or { f1, f2 } => self.children.extend_from_slice(&[f1, f2]),
+                Implies { f1, f2 } =>
+                    self.children.extend_from_slice(&[f1, f2]),
+                Iff { f1, f2 } => self.children.extend_from_slice(&[f1, f2]),
+                Not { f } => self.children.push(f),
                 Box { f, .. } => self.children.push(f),
                 Diamond { f, .. } => self.children.push(f),
                 Mu { f, .. } => self.children.push(f),
@@ -165,19 +658,58 @@ impl<'a> Iterator for Subformulas<'a> {
     }
 }
 
-impl FromStr for Formula {
+impl Parsed {
+    /// Checks that every free variable of the formula is among `bound`. The
+    /// error lists the offending occurrences by their position in the
+    /// source text.
+    pub fn check_closed(&self, bound: &[VarName]) -> Result<(), MyuError> {
+        let free = self
+            .free
+            .iter()
+            .filter(|(name, _)| !bound.contains(name))
+            .cloned()
+            .collect::<Vec<_>>();
+        if free.is_empty() {
+            Ok(())
+        } else {
+            Err(MyuError::OpenFormulaError(free))
+        }
+    }
+}
+
+impl FromStr for Parsed {
     type Err = String;
 
-    fn from_str(s: &str) -> Result<Formula, Self::Err> {
+    fn from_str(s: &str) -> Result<Parsed, Self::Err> {
         use combine::{eof, stream::position, EasyParser, Parser};
         parser::formula()
             .and(eof())
             .easy_parse(position::Stream::new(s))
-            .map(|((f, _), _)| f)
+            .map(|(((formula, free), _), _)| Parsed { formula, free })
             .map_err(|e| e.to_string())
     }
 }
 
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Formula, Self::Err> {
+        s.parse::<Parsed>().map(|parsed| parsed.formula)
+    }
+}
+
+impl From<combine::stream::position::SourcePosition> for Position {
+    fn from(pos: combine::stream::position::SourcePosition) -> Position {
+        Position { line: pos.line as usize, column: pos.column as usize }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}", self.line, self.column)
+    }
+}
+
 impl fmt::Display for Formula {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         use Formula::*;
@@ -186,8 +718,19 @@ impl fmt::Display for Formula {
             False => write!(fmt, "false"),
             True => write!(fmt, "true"),
             Var { name } => write!(fmt, "{}", name),
+            Not { f } => write!(fmt, "!{}", f),
+            And { f1, f2 } if f1.extends_right() =>
+                write!(fmt, "(({}) && {})", f1, f2),
             And { f1, f2 } => write!(fmt, "({} && {})", f1, f2),
+            Or { f1, f2 } if f1.extends_right() =>
+                write!(fmt, "(({}) || {})", f1, f2),
             Or { f1, f2 } => write!(fmt, "({} || {})", f1, f2),
+            Implies { f1, f2 } if f1.extends_right() =>
+                write!(fmt, "(({}) => {})", f1, f2),
+            Implies { f1, f2 } => write!(fmt, "({} => {})", f1, f2),
+            Iff { f1, f2 } if f1.extends_right() =>
+                write!(fmt, "(({}) <=> {})", f1, f2),
+            Iff { f1, f2 } => write!(fmt, "({} <=> {})", f1, f2),
             Diamond { step, f } => write!(fmt, "<{}>{}", step, f),
             Box { step, f } => write!(fmt, "[{}]{}", step, f),
             Mu { var, f } => write!(fmt, "mu {}. {}", var, f),
@@ -196,6 +739,77 @@ impl fmt::Display for Formula {
     }
 }
 
+impl fmt::Display for RegFormula {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use RegFormula::*;
+
+        match self {
+            Action { act } => write!(fmt, "{}", act),
+            Seq { r1, r2 } => {
+                match **r1 {
+                    Choice { .. } => write!(fmt, "({}).", r1)?,
+                    _ => write!(fmt, "{}.", r1)?,
+                }
+                match **r2 {
+                    Choice { .. } => write!(fmt, "({})", r2),
+                    _ => write!(fmt, "{}", r2),
+                }
+            },
+            Choice { r1, r2 } => write!(fmt, "{}+{}", r1, r2),
+            Star { r } if r.is_atomic() => write!(fmt, "{}*", r),
+            Star { r } => write!(fmt, "({})*", r),
+            Plus { r } if r.is_atomic() => write!(fmt, "{}+", r),
+            Plus { r } => write!(fmt, "({})+", r),
+        }
+    }
+}
+
+/// Whether a label can be written without quotes.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_lowercase())
+        && chars
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        && name != "true"
+        && name != "false"
+}
+
+impl fmt::Display for ActionFormula {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ActionFormula::*;
+
+        match self {
+            True => write!(fmt, "true"),
+            False => write!(fmt, "false"),
+            Label { name } if is_identifier(name) => write!(fmt, "{}", name),
+            Label { name } => write!(fmt, "\"{}\"", name),
+            Pattern { name, args } => {
+                write!(fmt, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{}", arg.as_ref().map_or("_", |arg| arg))?;
+                }
+                write!(fmt, ")")
+            },
+            Not { a } if a.is_atomic() => write!(fmt, "!{}", a),
+            Not { a } => write!(fmt, "!({})", a),
+            And { a1, a2 } => {
+                match **a1 {
+                    Or { .. } => write!(fmt, "({})&", a1)?,
+                    _ => write!(fmt, "{}&", a1)?,
+                }
+                match **a2 {
+                    Or { .. } => write!(fmt, "({})", a2),
+                    _ => write!(fmt, "{}", a2),
+                }
+            },
+            Or { a1, a2 } => write!(fmt, "{}|{}", a1, a2),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -248,22 +862,118 @@ mod tests {
         );
     }
 
+    #[test]
+    fn precedence() {
+        let var =
+            |name: &str| Box::new(Formula::Var { name: name.to_string() });
+        let parse = |s: &str| s.parse::<Formula>().unwrap();
+
+        assert_eq!(
+            parse("X && Y && Z"),
+            Formula::And {
+                f1: Box::new(Formula::And { f1: var("X"), f2: var("Y") }),
+                f2: var("Z"),
+            }
+        );
+        assert_eq!(
+            parse("X || Y && Z"),
+            Formula::Or {
+                f1: var("X"),
+                f2: Box::new(Formula::And { f1: var("Y"), f2: var("Z") }),
+            }
+        );
+        assert_eq!(
+            parse("!X && <a>Y || [b]Z"),
+            Formula::Or {
+                f1: Box::new(Formula::And {
+                    f1: Box::new(Formula::Not { f: var("X") }),
+                    f2: Box::new(Formula::Diamond {
+                        step: "a".into(),
+                        f: var("Y")
+                    }),
+                }),
+                f2: Box::new(Formula::Box { step: "b".into(), f: var("Z") }),
+            }
+        );
+        assert_eq!(
+            parse("X && mu Y. Y || X"),
+            Formula::And {
+                f1: var("X"),
+                f2: Box::new(Formula::Mu {
+                    var: "Y".to_string(),
+                    f: Box::new(Formula::Or { f1: var("Y"), f2: var("X") }),
+                }),
+            }
+        );
+        assert_eq!(parse("(((X)) && ((Y)))"), parse("X && Y"));
+        assert_eq!(parse("<a>(X || Y)"), parse("<a>((X) || Y)"));
+        assert_eq!(
+            parse("% comment\nX && % another comment\n  Y\n% trailing"),
+            parse("X && Y")
+        );
+
+        let f = parse("(<a>mu X. X) || !nu Y. Y && Y");
+        assert_eq!(f.to_string(), "((<a>mu X. X) || !nu Y. (Y && Y))");
+        assert_eq!(parse(&f.to_string()), f);
+    }
+
+    #[test]
+    fn implication() {
+        let var =
+            |name: &str| Box::new(Formula::Var { name: name.to_string() });
+        let parse = |s: &str| s.parse::<Formula>().unwrap();
+
+        assert_eq!(
+            parse("X => Y => Z"),
+            Formula::Implies {
+                f1: var("X"),
+                f2: Box::new(Formula::Implies { f1: var("Y"), f2: var("Z") }),
+            }
+        );
+        assert_eq!(
+            parse("X || Y => Z"),
+            Formula::Implies {
+                f1: Box::new(Formula::Or { f1: var("X"), f2: var("Y") }),
+                f2: var("Z"),
+            }
+        );
+        assert_eq!(
+            parse("X => Y <=> Z <=> X"),
+            Formula::Iff {
+                f1: Box::new(Formula::Iff {
+                    f1: Box::new(Formula::Implies {
+                        f1: var("X"),
+                        f2: var("Y")
+                    }),
+                    f2: var("Z"),
+                }),
+                f2: var("X"),
+            }
+        );
+
+        let f = parse("(mu X. X) => nu Y. Y <=> X");
+        assert_eq!(f.to_string(), "((mu X. X) => nu Y. (Y <=> X))");
+        assert_eq!(parse(&f.to_string()), f);
+        let parsed = "(mu X. X) => nu Y. Y <=> X".parse::<Parsed>().unwrap();
+        assert_eq!(
+            parsed.check_closed(&[]),
+            Err(MyuError::OpenFormulaError(vec![("X".to_string(), at(1, 26))]))
+        );
+    }
+
     #[test]
     fn modal_operators() {
         let f = "[tau]true".parse::<Formula>();
         assert_eq!(
             f,
-            Ok(Formula::Box {
-                step: "tau".to_string(),
-                f: Box::new(Formula::True),
-            })
+            Ok(Formula::Box { step: "tau".into(), f: Box::new(Formula::True) })
         );
 
         let f = "<tau>false".parse::<Formula>();
         assert_eq!(
             f,
             Ok(Formula::Diamond {
-                step: "tau".to_string(),
+                step: "tau".into(),
                 f: Box::new(Formula::False),
             })
         );
@@ -272,9 +982,9 @@ mod tests {
         assert_eq!(
             f,
             Ok(Formula::Box {
-                step: "tau".to_string(),
+                step: "tau".into(),
                 f: Box::new(Formula::Diamond {
-                    step: "tau".to_string(),
+                    step: "tau".into(),
                     f: Box::new(Formula::True)
                 }),
             })
@@ -284,23 +994,159 @@ mod tests {
         assert_eq!(
             f,
             Ok(Formula::Diamond {
-                step: "tau".to_string(),
+                step: "tau".into(),
                 f: Box::new(Formula::Box {
-                    step: "tau".to_string(),
+                    step: "tau".into(),
                     f: Box::new(Formula::False)
                 }),
             })
         );
     }
 
+    #[test]
+    fn regular_modalities() {
+        let action = |name: &str| Box::new(RegFormula::from(name));
+
+        let f = "<tau*.a>true".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Diamond {
+                step: RegFormula::Seq {
+                    r1: Box::new(RegFormula::Star { r: action("tau") }),
+                    r2: action("a"),
+                },
+                f: Box::new(Formula::True),
+            })
+        );
+
+        let f = "[a + b.c]false".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Box {
+                step: RegFormula::Choice {
+                    r1: action("a"),
+                    r2: Box::new(RegFormula::Seq {
+                        r1: action("b"),
+                        r2: action("c"),
+                    }),
+                },
+                f: Box::new(Formula::False),
+            })
+        );
+
+        let f = "[(tau)+]X".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Box {
+                step: RegFormula::Plus { r: action("tau") },
+                f: Box::new(Formula::Var { name: "X".to_string() }),
+            })
+        );
+
+        let f = "<(a+b)+.c*>true".parse::<Formula>().unwrap();
+        assert_eq!(f.to_string(), "<(a+b)+.c*>true");
+    }
+
+    #[test]
+    fn action_formulas() {
+        let label = |name: &str| Box::new(ActionFormula::from(name));
+
+        let f = "<true>true".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Diamond {
+                step: RegFormula::Action { act: ActionFormula::True },
+                f: Box::new(Formula::True),
+            })
+        );
+
+        let f = "[!(a | b)]false".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Box {
+                step: RegFormula::Action {
+                    act: ActionFormula::Not {
+                        a: Box::new(ActionFormula::Or {
+                            a1: label("a"),
+                            a2: label("b"),
+                        }),
+                    },
+                },
+                f: Box::new(Formula::False),
+            })
+        );
+
+        let f = "<!a & b|c>true".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Diamond {
+                step: RegFormula::Action {
+                    act: ActionFormula::Or {
+                        a1: Box::new(ActionFormula::And {
+                            a1: Box::new(ActionFormula::Not { a: label("a") }),
+                            a2: label("b"),
+                        }),
+                        a2: label("c"),
+                    },
+                },
+                f: Box::new(Formula::True),
+            })
+        );
+
+        let f = "<(a|b)*.(!tau.c)+>true".parse::<Formula>().unwrap();
+        assert_eq!(f.to_string(), "<(a|b)*.(!tau.c)+>true");
+    }
+
+    #[test]
+    fn action_labels() {
+        let f = "<\"lock(p1, f1)\">true".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Diamond {
+                step: "lock(p1, f1)".into(),
+                f: Box::new(Formula::True),
+            })
+        );
+
+        let f = "[lock(_, f(x,y))]false".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Box {
+                step: RegFormula::Action {
+                    act: ActionFormula::Pattern {
+                        name: "lock".to_string(),
+                        args: vec![None, Some("f(x,y)".to_string())],
+                    },
+                },
+                f: Box::new(Formula::False),
+            })
+        );
+
+        let f = "<\"eat(p1)\".free(_, f1)>true".parse::<Formula>().unwrap();
+        assert_eq!(f.to_string(), "<\"eat(p1)\".free(_, f1)>true");
+
+        let pattern = |s: &str| match s.parse::<Formula>() {
+            Ok(Formula::Diamond {
+                step: RegFormula::Action { act }, ..
+            }) => act,
+            _ => panic!("expected an action formula"),
+        };
+        assert!(pattern("<lock(_, f1)>true").matches("lock(p1, f1)"));
+        assert!(pattern("<lock(p1,f1)>true").matches("lock(p1, f1)"));
+        assert!(!pattern("<lock(_, f1)>true").matches("lock(p1, f2)"));
+        assert!(!pattern("<lock(_)>true").matches("lock(p1, f1)"));
+        assert!(pattern("<f(_, g(a, b))>true").matches("f(x, g(a,b))"));
+        assert!(!pattern("<lock>true").matches("lock(p1, f1)"));
+    }
+
     #[test]
     fn fixpoints() {
         let f = "mu X. X".parse::<Formula>();
         assert_eq!(
             f,
             Ok(Formula::Mu {
-                var: 'X',
-                f: Box::new(Formula::Var { name: 'X' }),
+                var: "X".to_string(),
+                f: Box::new(Formula::Var { name: "X".to_string() }),
             })
         );
 
@@ -308,8 +1154,8 @@ mod tests {
         assert_eq!(
             f,
             Ok(Formula::Nu {
-                var: 'Y',
-                f: Box::new(Formula::Var { name: 'Y' }),
+                var: "Y".to_string(),
+                f: Box::new(Formula::Var { name: "Y".to_string() }),
             })
         );
 
@@ -317,10 +1163,10 @@ mod tests {
         assert_eq!(
             f,
             Ok(Formula::Mu {
-                var: 'X',
+                var: "X".to_string(),
                 f: Box::new(Formula::Diamond {
-                    step: "tau".to_string(),
-                    f: Box::new(Formula::Var { name: 'X' })
+                    step: "tau".into(),
+                    f: Box::new(Formula::Var { name: "X".to_string() })
                 }),
             })
         );
@@ -329,12 +1175,12 @@ mod tests {
         assert_eq!(
             f,
             Ok(Formula::Mu {
-                var: 'X',
+                var: "X".to_string(),
                 f: Box::new(Formula::Nu {
-                    var: 'Y',
+                    var: "Y".to_string(),
                     f: Box::new(Formula::Or {
-                        f1: Box::new(Formula::Var { name: 'X' }),
-                        f2: Box::new(Formula::Var { name: 'Y' })
+                        f1: Box::new(Formula::Var { name: "X".to_string() }),
+                        f2: Box::new(Formula::Var { name: "Y".to_string() })
                     })
                 }),
             })
@@ -344,29 +1190,236 @@ mod tests {
         assert_eq!(
             f,
             Ok(Formula::Nu {
-                var: 'X',
+                var: "X".to_string(),
                 f: Box::new(Formula::And {
-                    f1: Box::new(Formula::Var { name: 'X' }),
+                    f1: Box::new(Formula::Var { name: "X".to_string() }),
                     f2: Box::new(Formula::Mu {
-                        var: 'Y',
-                        f: Box::new(Formula::Var { name: 'Y' })
+                        var: "Y".to_string(),
+                        f: Box::new(Formula::Var { name: "Y".to_string() })
                     })
                 })
             })
         );
     }
 
+    #[test]
+    fn variable_names() {
+        let f = "nu Reach. mu X1'. (<a>Reach || <b>X1')".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Nu {
+                var: "Reach".to_string(),
+                f: Box::new(Formula::Mu {
+                    var: "X1'".to_string(),
+                    f: Box::new(Formula::Or {
+                        f1: Box::new(Formula::Diamond {
+                            step: "a".into(),
+                            f: Box::new(Formula::Var {
+                                name: "Reach".to_string()
+                            }),
+                        }),
+                        f2: Box::new(Formula::Diamond {
+                            step: "b".into(),
+                            f: Box::new(Formula::Var {
+                                name: "X1'".to_string()
+                            }),
+                        }),
+                    }),
+                }),
+            })
+        );
+
+        let vars = (0..30).map(|i| format!("X_{}", i)).collect::<Vec<_>>();
+        let f = vars.iter().fold(String::from("true"), |f, x| {
+            format!("mu {}. ({} && {})", x, x, f)
+        });
+        let f = f.parse::<Formula>().unwrap();
+        assert_eq!(f.nesting_depth(), 30);
+        assert_eq!(f.to_string().parse::<Formula>(), Ok(f));
+    }
+
+    #[test]
+    fn negation() {
+        let f = "!<a>true".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Not {
+                f: Box::new(Formula::Diamond {
+                    step: "a".into(),
+                    f: Box::new(Formula::True),
+                }),
+            })
+        );
+
+        let f = "not nu X. X".parse::<Formula>();
+        assert_eq!(
+            f,
+            Ok(Formula::Not {
+                f: Box::new(Formula::Nu {
+                    var: "X".to_string(),
+                    f: Box::new(Formula::Var { name: "X".to_string() }),
+                }),
+            })
+        );
+
+        let f = "nu X. notX".parse::<Formula>();
+        assert!(f.is_err());
+    }
+
+    #[test]
+    fn positivity() {
+        let f = "mu X. !!X".parse::<Formula>().unwrap();
+        assert_eq!(f.check_positive(), Ok(()));
+
+        let f = "mu X. !<a>X".parse::<Formula>().unwrap();
+        assert_eq!(
+            f.check_positive(),
+            Err(MyuError::PositivityError("X".to_string()))
+        );
+
+        let f = "mu X. !nu Y. (!X || !Y)".parse::<Formula>().unwrap();
+        assert_eq!(
+            f.check_positive(),
+            Err(MyuError::PositivityError("Y".to_string()))
+        );
+
+        let f = "mu X. !nu Y. !(X || !Y)".parse::<Formula>().unwrap();
+        assert_eq!(f.check_positive(), Ok(()));
+
+        let f = "mu X. (!X => <a>X)".parse::<Formula>().unwrap();
+        assert_eq!(f.check_positive(), Ok(()));
+
+        let f = "nu X. (X => <a>true)".parse::<Formula>().unwrap();
+        assert_eq!(
+            f.check_positive(),
+            Err(MyuError::PositivityError("X".to_string()))
+        );
+
+        let f = "nu X. ((mu Y. <a>Y) <=> [b]X)".parse::<Formula>().unwrap();
+        assert_eq!(
+            f.check_positive(),
+            Err(MyuError::PositivityError("X".to_string()))
+        );
+    }
+
+    #[test]
+    fn positive_normal_form() {
+        let f = "!(<a>true || [b]false)".parse::<Formula>().unwrap();
+        let expected = "([a]false && <b>true)".parse::<Formula>().unwrap();
+        assert_eq!(f.to_pnf(), expected);
+
+        let f = "!mu X. (<a>X && !nu Y. !X)".parse::<Formula>().unwrap();
+        let expected = "nu X. ([a]X || nu Y. X)".parse::<Formula>().unwrap();
+        assert_eq!(f.to_pnf(), expected);
+
+        let f = "nu X. (<a>true => [b]X)".parse::<Formula>().unwrap();
+        let expected = "nu X. ([a]false || [b]X)".parse::<Formula>().unwrap();
+        assert_eq!(f.to_pnf(), expected);
+
+        let f = "<a>true <=> !Y".parse::<Formula>().unwrap();
+        let expected =
+            "([a]false || !Y) && (Y || <a>true)".parse::<Formula>().unwrap();
+        assert_eq!(f.to_pnf(), expected);
+    }
+
+    fn at(line: usize, column: usize) -> Position {
+        Position { line, column }
+    }
+
+    #[test]
+    fn closedness() {
+        let f = "nu X. <a>X".parse::<Parsed>().unwrap();
+        assert_eq!(f.check_closed(&[]), Ok(()));
+
+        let f = "X || mu Y. !<a>(Y && [b]Z)".parse::<Parsed>().unwrap();
+        assert_eq!(
+            f.check_closed(&[]),
+            Err(MyuError::OpenFormulaError(vec![
+                ("X".to_string(), at(1, 1)),
+                ("Z".to_string(), at(1, 25))
+            ]))
+        );
+        assert_eq!(
+            f.check_closed(&["X".to_string()]),
+            Err(MyuError::OpenFormulaError(vec![("Z".to_string(), at(1, 25))]))
+        );
+        assert_eq!(f.check_closed(&["X".to_string(), "Z".to_string()]), Ok(()));
+
+        let f = "mu X. X && X".parse::<Parsed>().unwrap();
+        assert_eq!(f.check_closed(&[]), Ok(()));
+
+        let f = "(mu X. X) && X".parse::<Parsed>().unwrap();
+        assert_eq!(
+            f.check_closed(&[]),
+            Err(MyuError::OpenFormulaError(vec![("X".to_string(), at(1, 14))]))
+        );
+
+        // Positions refer to the input, including comments and line breaks.
+        let f = "% comment\nnu X.\n   (<a>X &&   Y)".parse::<Parsed>().unwrap();
+        assert_eq!(
+            f.check_closed(&[]),
+            Err(MyuError::OpenFormulaError(vec![("Y".to_string(), at(3, 15))]))
+        );
+    }
+
+    #[test]
+    fn renaming() {
+        let f = "mu X. (X || nu X. X)".parse::<Formula>().unwrap();
+        let expected = "mu X. (X || nu X'. X')".parse::<Formula>().unwrap();
+        assert_eq!(f.rename_bound(), expected);
+
+        let f = "(X || mu X. (nu X'. (X' && nu X. X) || mu X. X))"
+            .parse::<Formula>()
+            .unwrap();
+        let expected =
+            "(X || mu X'. (nu X''. (X'' && nu X'''. X''') || mu X''''. X''''))"
+                .parse::<Formula>()
+                .unwrap();
+        assert_eq!(f.rename_bound(), expected);
+
+        let f = "mu X. nu Y. (X || Y)".parse::<Formula>().unwrap();
+        assert_eq!(f.rename_bound(), f);
+    }
+
+    #[test]
+    fn lowering() {
+        let f = "<tau*.a>true".parse::<Formula>().unwrap();
+        let expected = "mu Z. (<a>true || <tau>Z)".parse::<Formula>().unwrap();
+        assert_eq!(f.lower_regular(), expected);
+
+        let f = "nu Z. [(a+b)+]Z".parse::<Formula>().unwrap();
+        let expected = "nu Z. (([a]nu Z'. (Z && ([a]Z' && [b]Z'))) \
+                        && [b]nu Z'. (Z && ([a]Z' && [b]Z')))"
+            .parse::<Formula>()
+            .unwrap();
+        assert_eq!(f.lower_regular(), expected);
+    }
+
     #[test]
     fn depth_measures() {
-        let f = "(mu X.nu Y.(X||Y)&& mu V. mu W. (V && mu Z.(true || Z)))"
+        let f = "(mu X.nu Y.(X||Y))&& mu V. mu W. (V && mu Z.(true || Z))"
             .parse::<Formula>()
             .unwrap();
         assert_eq!(f.nesting_depth(), 3);
 
-        let f = "(mu X.nu Y.(X||Y)&& mu V. nu W. (V && mu Z.(true || Z)))"
+        let f = "(mu X.nu Y.(X||Y))&& mu V. nu W. (V && mu Z.(true || Z))"
             .parse::<Formula>()
             .unwrap();
         assert_eq!(f.alternation_depth(), 3);
         assert_eq!(f.dependent_ad(), 2);
     }
+
+    #[test]
+    fn open_fixpoints() {
+        let vars = |f: &str| {
+            let f = f.parse::<Formula>().unwrap();
+            f.open_fixpoints().cloned().collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vars("mu X. (nu Y. (X || Y) && mu V. (X || mu W. (V && W)))"),
+            vec!["V".to_string(), "W".to_string()]
+        );
+        assert_eq!(vars("nu Y. (X || Y && mu Z. <a>Z)"), vec!["Y".to_string()]);
+        assert_eq!(vars("mu Z. <a>Z"), Vec::<VarName>::new());
+    }
 }
@@ -1,84 +1,386 @@
-use crate::mu_calculus::Formula;
+use crate::mu_calculus::{
+    ActionFormula, Formula, Position, RegFormula, VarName,
+};
 use combine::{
-    between, choice,
+    attempt, between, chainl1, choice,
     error::ParseError,
-    parser,
+    many, not_followed_by, optional, parser,
     parser::{
-        char::{char, newline, space, spaces, string, upper},
+        char::{alpha_num, char, space, spaces, string},
+        range::{recognize, take_while, take_while1},
         regex::find,
-        repeat::skip_until,
     },
-    skip_many1,
-    stream::RangeStream,
+    position, satisfy, sep_by1, skip_many, skip_many1,
+    stream::{position::SourcePosition, RangeStream},
     Parser,
 };
 use regex::Regex;
 
+/// A formula together with its free variable occurrences, in the order in
+/// which they appear in the input.
+pub type Located = (Formula, Vec<(VarName, Position)>);
+
+parser! {
+    pub fn formula['a, I]()(I) -> Located
+    where [I: RangeStream<Token=char, Range=&'a str, Position=SourcePosition>
+           + 'a,
+       I::Error: ParseError<I::Token, I::Range, I::Position>,]
+    {
+    whitespace().with(binary())
+    }
+}
+
 parser! {
-    pub fn formula['a, I]()(I) -> Formula
+    fn regular['a, I]()(I) -> RegFormula
     where [I: RangeStream<Token=char, Range=&'a str> + 'a,
        I::Error: ParseError<I::Token, I::Range, I::Position>,]
     {
-    formula_()
+    regular_()
     }
 }
 
-fn formula_<'a, I>() -> impl Parser<I, Output = Formula> + 'a
+fn regular_<'a, I>() -> impl Parser<I, Output = RegFormula> + 'a
 where
     I: RangeStream<Token = char, Range = &'a str> + 'a,
     I::Error: ParseError<I::Token, I::Range, I::Position>,
 {
-    let true_lit = string("true").map(|_| Formula::True);
-    let false_lit = string("false").map(|_| Formula::False);
-    let var = upper().map(|c| Formula::Var { name: c });
-    let boolean_op = between(
-        char('('),
-        char(')'),
-        (formula(), string("&&").or(string("||")), formula()),
+    // Parentheses may enclose either an action formula or a regular formula,
+    // so the former is attempted first.
+    let atom = choice((
+        attempt(action()).map(|act| RegFormula::Action { act }),
+        between(char('(').skip(spaces()), char(')'), regular()),
+    ))
+    .skip(spaces());
+    // A `+` is only a postfix operator when no operand follows it; otherwise
+    // it denotes a choice.
+    let operand_start =
+        || satisfy(|c: char| c.is_ascii_lowercase() || c == '(' || c == '!');
+    let postfix_op = choice((
+        char('*'),
+        attempt(
+            char('+').skip(not_followed_by(spaces().with(operand_start()))),
+        ),
+    ))
+    .skip(spaces());
+    let postfix = (atom, many(postfix_op)).map(|(r, ops): (_, Vec<_>)| {
+        ops.into_iter().fold(r, |r, op| match op {
+            '*' => RegFormula::Star { r: Box::new(r) },
+            _ => RegFormula::Plus { r: Box::new(r) },
+        })
+    });
+    let seq = chainl1(
+        postfix,
+        char('.').skip(spaces()).map(|_| {
+            |r1, r2| RegFormula::Seq { r1: Box::new(r1), r2: Box::new(r2) }
+        }),
+    );
+    chainl1(
+        seq,
+        char('+').skip(spaces()).map(|_| {
+            |r1, r2| RegFormula::Choice { r1: Box::new(r1), r2: Box::new(r2) }
+        }),
+    )
+}
+
+parser! {
+    fn action['a, I]()(I) -> ActionFormula
+    where [I: RangeStream<Token=char, Range=&'a str> + 'a,
+       I::Error: ParseError<I::Token, I::Range, I::Position>,]
+    {
+    action_()
+    }
+}
+
+fn action_<'a, I>() -> impl Parser<I, Output = ActionFormula> + 'a
+where
+    I: RangeStream<Token = char, Range = &'a str> + 'a,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+{
+    let conjunction = chainl1(
+        action_unary(),
+        char('&').skip(spaces()).map(|_| {
+            |a1, a2| ActionFormula::And { a1: Box::new(a1), a2: Box::new(a2) }
+        }),
+    );
+    chainl1(
+        conjunction,
+        char('|').skip(spaces()).map(|_| {
+            |a1, a2| ActionFormula::Or { a1: Box::new(a1), a2: Box::new(a2) }
+        }),
     )
-    .map(|(f1, op, f2)| match op {
-        "&&" => Formula::And { f1: Box::new(f1), f2: Box::new(f2) },
-        "||" => Formula::Or { f1: Box::new(f1), f2: Box::new(f2) },
-        _ => unreachable!(),
+}
+
+parser! {
+    fn action_unary['a, I]()(I) -> ActionFormula
+    where [I: RangeStream<Token=char, Range=&'a str> + 'a,
+       I::Error: ParseError<I::Token, I::Range, I::Position>,]
+    {
+    action_unary_()
+    }
+}
+
+fn action_unary_<'a, I>() -> impl Parser<I, Output = ActionFormula> + 'a
+where
+    I: RangeStream<Token = char, Range = &'a str> + 'a,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+{
+    let label = Regex::new(r"^[a-z][a-z0-9_]*").unwrap();
+    let arg = data_term().map(|t: &'a str| match t {
+        "_" => None,
+        _ => Some(t.to_owned()),
     });
-    let action = Regex::new(r"^[a-z][a-z0-9_]*").unwrap();
+    let args = between(
+        char('(').skip(spaces()),
+        char(')'),
+        sep_by1(arg.skip(spaces()), char(',').skip(spaces())),
+    );
+    let literal = (find(label), optional(args)).map(
+        |(name, args): (&'a str, Option<Vec<_>>)| match (name, args) {
+            ("true", None) => ActionFormula::True,
+            ("false", None) => ActionFormula::False,
+            (name, None) => ActionFormula::from(name),
+            (name, Some(args)) =>
+                ActionFormula::Pattern { name: name.to_owned(), args },
+        },
+    );
+    let quoted = between(char('"'), char('"'), take_while(|c: char| c != '"'))
+        .map(|name: &'a str| ActionFormula::from(name));
+    let negation = char('!')
+        .skip(spaces())
+        .with(action_unary())
+        .map(|a| ActionFormula::Not { a: Box::new(a) });
+    choice((
+        literal,
+        quoted,
+        negation,
+        between(char('(').skip(spaces()), char(')'), action()),
+    ))
+    .skip(spaces())
+}
+
+parser! {
+    fn data_term['a, I]()(I) -> &'a str
+    where [I: RangeStream<Token=char, Range=&'a str> + 'a,
+       I::Error: ParseError<I::Token, I::Range, I::Position>,]
+    {
+    data_term_()
+    }
+}
+
+/// Parses an argument of an action label, which may itself be an
+/// application of a function to arguments.
+fn data_term_<'a, I>() -> impl Parser<I, Output = &'a str> + 'a
+where
+    I: RangeStream<Token = char, Range = &'a str> + 'a,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+{
+    let args = between(
+        char('(').skip(spaces()),
+        char(')'),
+        sep_by1::<Vec<_>, _, _, _>(
+            data_term().skip(spaces()),
+            char(',').skip(spaces()),
+        ),
+    );
+    recognize((
+        take_while1(|c: char| c.is_alphanumeric() || c == '_' || c == '\''),
+        optional(args),
+    ))
+}
+
+parser! {
+    fn binary['a, I]()(I) -> Located
+    where [I: RangeStream<Token=char, Range=&'a str, Position=SourcePosition>
+           + 'a,
+       I::Error: ParseError<I::Token, I::Range, I::Position>,]
+    {
+    binary_()
+    }
+}
+
+/// Parses unary formulas joined by binary connectives. This parser and
+/// `unary` expect leading whitespace to be skipped already and skip trailing
+/// whitespace themselves, which keeps whitespace parsers off the recursion
+/// path.
+fn binary_<'a, I>() -> impl Parser<I, Output = Located> + 'a
+where
+    I: RangeStream<Token = char, Range = &'a str, Position = SourcePosition>
+        + 'a,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+{
+    // All binary connectives are parsed as one flat chain, since a separate
+    // level per connective deepens the recursion for every nested formula.
+    let op = choice((
+        string("&&").map(|_| Connective::And),
+        string("||").map(|_| Connective::Or),
+        string("=>").map(|_| Connective::Implies),
+        string("<=>").map(|_| Connective::Iff),
+    ))
+    .skip(whitespace());
+    (unary(), many((op, unary()))).map(
+        |((f, mut free), rest): (Located, Vec<(Connective, Located)>)| {
+            let mut operands = vec![f];
+            let mut connectives = Vec::new();
+            for (op, (g, g_free)) in rest {
+                connectives.push(op);
+                operands.push(g);
+                free.extend(g_free);
+            }
+            (Connective::apply_all(operands, connectives), free)
+        },
+    )
+}
+
+/// The binary connectives, from the one binding strongest to the one binding
+/// weakest.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Connective {
+    And,
+    Or,
+    Implies,
+    Iff,
+}
+
+impl Connective {
+    fn apply(self, f1: Formula, f2: Formula) -> Formula {
+        let (f1, f2) = (Box::new(f1), Box::new(f2));
+        match self {
+            Connective::And => Formula::And { f1, f2 },
+            Connective::Or => Formula::Or { f1, f2 },
+            Connective::Implies => Formula::Implies { f1, f2 },
+            Connective::Iff => Formula::Iff { f1, f2 },
+        }
+    }
+
+    /// Joins `operands[i]` and `operands[i + 1]` by `connectives[i]`,
+    /// grouping the operands of stronger connectives first. Implications
+    /// associate to the right and all other connectives to the left.
+    fn apply_all(
+        mut operands: Vec<Formula>,
+        mut connectives: Vec<Connective>,
+    ) -> Formula {
+        use Connective::*;
+        for &level in &[And, Or, Implies, Iff] {
+            let mut rest = operands.into_iter();
+            let mut runs = vec![vec![rest.next().unwrap()]];
+            let mut weaker = Vec::new();
+            for (op, f) in connectives.into_iter().zip(rest) {
+                if op == level {
+                    runs.last_mut().unwrap().push(f);
+                } else {
+                    weaker.push(op);
+                    runs.push(vec![f]);
+                }
+            }
+            operands = runs.into_iter().map(|run| level.fold(run)).collect();
+            connectives = weaker;
+        }
+        operands.pop().unwrap()
+    }
+
+    fn fold(self, run: Vec<Formula>) -> Formula {
+        if self == Connective::Implies {
+            let mut run = run.into_iter().rev();
+            let last = run.next().unwrap();
+            run.fold(last, |f2, f1| self.apply(f1, f2))
+        } else {
+            let mut run = run.into_iter();
+            let first = run.next().unwrap();
+            run.fold(first, |f1, f2| self.apply(f1, f2))
+        }
+    }
+}
+
+parser! {
+    fn unary['a, I]()(I) -> Located
+    where [I: RangeStream<Token=char, Range=&'a str, Position=SourcePosition>
+           + 'a,
+       I::Error: ParseError<I::Token, I::Range, I::Position>,]
+    {
+    unary_()
+    }
+}
+
+/// Parses a formula that is not a binary operator application, unless it is
+/// enclosed in parentheses. Modalities and negations apply to the smallest
+/// formula following them, whereas fixpoints extend as far as possible.
+fn unary_<'a, I>() -> impl Parser<I, Output = Located> + 'a
+where
+    I: RangeStream<Token = char, Range = &'a str, Position = SourcePosition>
+        + 'a,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+{
+    let true_lit =
+        string("true").skip(whitespace()).map(|_| (Formula::True, Vec::new()));
+    let false_lit = string("false")
+        .skip(whitespace())
+        .map(|_| (Formula::False, Vec::new()));
+    let var_name = || {
+        find(Regex::new(r"^[A-Z][A-Za-z0-9_']*").unwrap())
+            .map(|name: &'a str| name.to_owned())
+    };
+    let var = (position(), var_name().skip(whitespace())).map(
+        |(pos, name): (_, VarName)| {
+            let free = vec![(name.clone(), Position::from(pos))];
+            (Formula::Var { name }, free)
+        },
+    );
+    let parenthesized = between(
+        char('(').skip(whitespace()),
+        char(')').skip(whitespace()),
+        binary(),
+    );
     let modal = |open, close| {
-        between(char(open), char(close), find(action.clone())).and(formula())
+        between(char(open), char(close), spaces().with(regular()))
+            .skip(whitespace())
+            .and(unary())
     };
-    let diamond_modal = modal('<', '>').map(|(step, f): (&'a str, Formula)| {
-        Formula::Diamond { step: step.to_owned(), f: Box::new(f) }
-    });
-    let box_modal = modal('[', ']').map(|(step, f): (&'a str, Formula)| {
-        Formula::Box { step: step.to_owned(), f: Box::new(f) }
+    let diamond_modal = modal('<', '>').map(|(step, (f, free))| {
+        (Formula::Diamond { step, f: Box::new(f) }, free)
     });
-    let fixpoint = |sigma| {
+    let box_modal = modal('[', ']')
+        .map(|(step, (f, free))| (Formula::Box { step, f: Box::new(f) }, free));
+    let negation = char('!')
+        .map(|_| ())
+        .or(attempt(string("not").skip(not_followed_by(alpha_num())))
+            .map(|_| ()))
+        .skip(whitespace())
+        .with(unary())
+        .map(|(f, free)| (Formula::Not { f: Box::new(f) }, free));
+    // The occurrences of the bound variable in the body are not free.
+    let fixpoint = |sigma, build: fn(VarName, Box<Formula>) -> Formula| {
         (
             string(sigma).skip(skip_many1(space())),
-            upper().skip(spaces()),
-            char('.'),
-            formula(),
+            var_name().skip(spaces()),
+            char('.').skip(whitespace()),
+            binary(),
         )
+            .map(
+                move |(_, var, _, (g, mut free)): (_, VarName, _, Located)| {
+                    free.retain(|(name, _)| *name != var);
+                    (build(var, Box::new(g)), free)
+                },
+            )
     };
-    let mu = fixpoint("mu")
-        .map(|(_, var, _, g)| Formula::Mu { var, f: Box::new(g) });
-    let nu = fixpoint("nu")
-        .map(|(_, var, _, g)| Formula::Nu { var, f: Box::new(g) });
-    let comment =
-        char('%').and(skip_until(newline())).and(formula()).map(|(_, f)| f);
-
-    between(
-        spaces(),
-        spaces(),
-        choice((
-            true_lit,
-            false_lit,
-            var,
-            boolean_op,
-            diamond_modal,
-            box_modal,
-            mu,
-            nu,
-            comment,
-        )),
-    )
+    let mu = fixpoint("mu", |var, f| Formula::Mu { var, f });
+    let nu = fixpoint("nu", |var, f| Formula::Nu { var, f });
+
+    // A choice between many alternatives takes a large stack frame, so the
+    // alternatives are split up and those that recurse the most come last,
+    // where they are reached through the fewest frames.
+    choice((true_lit, false_lit, var))
+        .or(choice((diamond_modal, box_modal, negation)))
+        .or(mu)
+        .or(nu)
+        .or(parenthesized)
+}
+
+/// Skips whitespace and comments, which run from `%` to the end of the line.
+fn whitespace<'a, I>() -> impl Parser<I, Output = ()> + 'a
+where
+    I: RangeStream<Token = char, Range = &'a str> + 'a,
+    I::Error: ParseError<I::Token, I::Range, I::Position>,
+{
+    let comment = char('%').with(skip_many(satisfy(|c: char| c != '\n')));
+    skip_many(space().map(|_| ()).or(comment))
 }
@@ -1,66 +1,199 @@
 use crate::{
+    checker::{ModelChecker, Options},
     lts::{self, Lts},
-    mu_calculus as mc,
-};
-use std::{
-    collections::{BTreeSet, HashMap},
-    sync::atomic::Ordering,
+    modal, mu_calculus as mc,
+    stats::Stats,
 };
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    // Variables are looked up by name, so shadowing binders must be renamed.
+    let f = Arc::new(f.rename_bound());
+    let mut stats = Stats::with_subformulas(f.clone());
+    let env = Env::Valuation(valuation);
+    let result = eval_inner(lts, &f, &env, &modal::Cache::new(), &mut stats);
+    (result, stats)
+}
+
+/// Computes the least fixpoint of `body` in `var` by approximation, and
+/// returns the rank of every state in it: the index of the first approximant
+/// that contains the state, where approximant 0 is empty. The variables
+/// bound in `body` must have been renamed apart.
+pub fn ranks(
+    lts: &Lts,
+    var: &mc::VarName,
+    body: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> HashMap<lts::State, usize> {
+    let mut stats = Stats::new();
+    let modalities = modal::Cache::new();
+    let outer = Env::Valuation(valuation);
+    let mut ranks = HashMap::new();
+    let mut approx = lts::StateSet::new();
+    for rank in 1.. {
+        let env = Env::Bind { var, value: &approx, outer: &outer };
+        let next = eval_inner(lts, body, &env, &modalities, &mut stats);
+        if next == approx {
+            break;
+        }
+        ranks.extend((&next - &approx).iter().map(|s| (s, rank)));
+        approx = next;
+    }
+    ranks
+}
+
+pub struct Naive;
+
+/// The values of the variables: the valuation, extended by the current
+/// approximation of every enclosing fixpoint. A fixpoint adds its binding on
+/// top of the outer ones instead of copying them, and the binding stays
+/// local to the fixpoint, so the operands of a connective can be evaluated
+/// independently.
+enum Env<'a> {
+    Valuation(&'a mc::Valuation),
+    Bind { var: &'a mc::VarName, value: &'a lts::StateSet, outer: &'a Env<'a> },
+}
+
+impl Env<'_> {
+    fn get(&self, name: &mc::VarName) -> &lts::StateSet {
+        match self {
+            Env::Valuation(valuation) => &valuation[name],
+            Env::Bind { var, value, .. } if *var == name => value,
+            Env::Bind { outer, .. } => outer.get(name),
+        }
+    }
+}
+
+impl ModelChecker for Naive {
+    fn name(&self) -> &'static str {
+        "naive"
+    }
 
-pub fn eval(lts: &Lts, f: &mc::Formula) -> BTreeSet<lts::State> {
-    let mut env = HashMap::new();
-    eval_inner(lts, f, &mut env)
+    fn description(&self) -> &'static str {
+        "Fixpoint iteration, starting every fixpoint from scratch"
+    }
+
+    fn steps(&self) -> &'static str {
+        "fixpoint iterations"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+/// Evaluates both operands of a binary connective, in parallel. The second
+/// operand records its statistics separately, which are merged at the join.
+fn eval_both(
+    lts: &Lts,
+    f1: &mc::Formula,
+    f2: &mc::Formula,
+    env: &Env,
+    modalities: &modal::Cache,
+    stats: &mut Stats,
+) -> (lts::StateSet, lts::StateSet) {
+    let mut branch = stats.branch();
+    let sats = rayon::join(
+        || eval_inner(lts, f1, env, modalities, stats),
+        || eval_inner(lts, f2, env, modalities, &mut branch),
+    );
+    stats.merge(branch);
+    sats
 }
 
 fn eval_inner(
     lts: &Lts,
     f: &mc::Formula,
-    env: &mut HashMap<mc::VarName, BTreeSet<lts::State>>,
-) -> BTreeSet<lts::State> {
+    env: &Env,
+    modalities: &modal::Cache,
+    stats: &mut Stats,
+) -> lts::StateSet {
     use mc::Formula::*;
+    let start = Instant::now();
 
-    match f {
-        Var { name } => env[&name].clone(),
+    let result = match f {
+        Var { name } => env.get(name).clone(),
         True => lts.states().clone(),
-        False => BTreeSet::new(),
-        And { f1, f2 } => eval_inner(lts, f1, env)
-            .intersection(&eval_inner(lts, f2, env))
-            .cloned()
-            .collect(),
-        Or { f1, f2 } => eval_inner(lts, f1, env)
-            .union(&eval_inner(lts, f2, env))
-            .cloned()
-            .collect(),
+        False => lts::StateSet::new(),
+        Not { f: g } =>
+            lts.states() - &eval_inner(lts, g, env, modalities, stats),
+        And { f1, f2 } => {
+            let (sat1, sat2) = eval_both(lts, f1, f2, env, modalities, stats);
+            &sat1 & &sat2
+        },
+        Or { f1, f2 } => {
+            let (sat1, sat2) = eval_both(lts, f1, f2, env, modalities, stats);
+            &sat1 | &sat2
+        },
+        Implies { f1, f2 } => {
+            let (sat1, sat2) = eval_both(lts, f1, f2, env, modalities, stats);
+            &(lts.states() - &sat1) | &sat2
+        },
+        Iff { f1, f2 } => {
+            let (sat1, sat2) = eval_both(lts, f1, f2, env, modalities, stats);
+            let both = &sat1 & &sat2;
+            let neither = &(lts.states() - &sat1) - &sat2;
+            &both | &neither
+        },
         Diamond { step, f: g } => {
-            let sat = eval_inner(lts, g, env);
-            lts.step_transitions(step)
-                .filter(|(_s, ts)| ts.iter().any(|t| sat.contains(t)))
-                .map(|(s, _ts)| s)
-                .collect()
+            let sat = eval_inner(lts, g, env, modalities, stats);
+            stats.modality();
+            modal::possibly(lts, step, &sat, modalities)
         },
         Box { step, f: g } => {
-            let sat = eval_inner(lts, g, env);
-            lts.step_transitions(step)
-                .filter(|(_s, ts)| ts.iter().all(|t| sat.contains(t)))
-                .map(|(s, _ts)| s)
-                .collect()
+            let sat = eval_inner(lts, g, env, modalities, stats);
+            stats.modality();
+            modal::necessarily(lts, step, &sat, modalities)
         },
         Mu { var, f: g } | Nu { var, f: g } => {
-            let var_init = match f {
-                Mu { .. } => BTreeSet::new(),
+            let mut approx = match f {
+                Mu { .. } => lts::StateSet::new(),
                 Nu { .. } => lts.states().clone(),
                 _ => unreachable!(),
             };
-            let _ = env.insert(*var, var_init);
             loop {
-                super::ITERATIONS.fetch_add(1, Ordering::SeqCst);
-                let new = eval_inner(lts, g, env);
-                let prev = env.insert(*var, new).unwrap();
-                if prev == env[var] {
-                    break prev;
+                stats.iteration(var);
+                let env = Env::Bind { var, value: &approx, outer: env };
+                let new = eval_inner(lts, g, &env, modalities, stats);
+                if new == approx {
+                    break new;
                 }
+                approx = new;
             }
         },
+    };
+    stats.evaluated(f, result.len(), start.elapsed());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the results do not depend on the number of threads.
+    mod parallel {
+        use super::*;
+
+        fn eval(
+            lts: &Lts,
+            f: &mc::Formula,
+            valuation: &mc::Valuation,
+        ) -> (lts::StateSet, Stats) {
+            let pool =
+                rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+            pool.install(|| super::super::eval(lts, f, valuation))
+        }
+
+        generate_tests!();
     }
 }
 
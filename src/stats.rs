@@ -0,0 +1,310 @@
+use crate::mu_calculus as mc;
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+/// The statistics of a single run of a model checker. Evaluators that work
+/// on sets of states also record every evaluation of a subformula.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Stats {
+    /// The number of steps taken, as described by `ModelChecker::steps`.
+    pub steps: u64,
+    /// The number of iterations or unfoldings of every fixpoint, by its
+    /// variable.
+    pub iterations: BTreeMap<mc::VarName, u64>,
+    /// How often a modality was evaluated, by checkers that evaluate
+    /// modalities rather than solving a game.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalities: Option<u64>,
+    /// How often the result of a subformula was reused.
+    pub cache_hits: u64,
+    #[serde(serialize_with = "named", skip_serializing_if = "Option::is_none")]
+    subformulas: Option<Subformulas>,
+    /// The wall time of the whole run.
+    #[serde(serialize_with = "seconds")]
+    pub time: Duration,
+}
+
+/// The evaluations of the subformulas of a formula. They are only formatted
+/// when the statistics are rendered.
+#[derive(Clone, Debug)]
+struct Subformulas {
+    formula: Arc<mc::Formula>,
+    /// The position of every subformula in `formula.subformulas()`, keyed by
+    /// its address.
+    index: Arc<HashMap<usize, usize>>,
+    /// The evaluations recorded so far, by position.
+    evaluations: HashMap<usize, Subformula>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Subformula {
+    pub formula: String,
+    pub evaluations: u64,
+    /// The number of states satisfying the subformula after its last
+    /// evaluation.
+    pub states: usize,
+    /// The wall time spent evaluating the subformula, including its
+    /// operands.
+    #[serde(serialize_with = "seconds")]
+    pub time: Duration,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Statistics that count the evaluations of modalities.
+    pub fn with_modalities() -> Stats {
+        Stats { modalities: Some(0), ..Stats::default() }
+    }
+
+    /// Statistics that also list the subformulas of `f` in the order in
+    /// which `subformulas` visits them, whether or not they are evaluated.
+    pub fn with_subformulas(f: Arc<mc::Formula>) -> Stats {
+        let index = f
+            .subformulas()
+            .enumerate()
+            .map(|(i, g)| (g as *const _ as usize, i))
+            .collect();
+        let subformulas = Subformulas {
+            formula: f,
+            index: Arc::new(index),
+            evaluations: HashMap::new(),
+        };
+        Stats { subformulas: Some(subformulas), ..Stats::with_modalities() }
+    }
+
+    /// Empty statistics recording the same things as these, for one branch
+    /// of a parallel evaluation.
+    pub fn branch(&self) -> Stats {
+        let subformulas = self.subformulas.as_ref().map(|s| Subformulas {
+            formula: s.formula.clone(),
+            index: s.index.clone(),
+            evaluations: HashMap::new(),
+        });
+        Stats {
+            modalities: self.modalities.map(|_| 0),
+            subformulas,
+            ..Stats::default()
+        }
+    }
+
+    /// Adds the statistics of a branch to these.
+    pub fn merge(&mut self, branch: Stats) {
+        self.steps += branch.steps;
+        for (var, n) in branch.iterations {
+            *self.iterations.entry(var).or_insert(0) += n;
+        }
+        if let Some(n) = branch.modalities {
+            *self.modalities.get_or_insert(0) += n;
+        }
+        self.cache_hits += branch.cache_hits;
+        if let (Some(subformulas), Some(other)) =
+            (&mut self.subformulas, branch.subformulas)
+        {
+            for (i, g) in other.evaluations {
+                let entry = subformulas.evaluations.entry(i).or_default();
+                entry.evaluations += g.evaluations;
+                entry.states = g.states;
+                entry.time += g.time;
+            }
+        }
+    }
+
+    /// Records an iteration of the fixpoint binding `var`.
+    pub fn iteration(&mut self, var: &mc::VarName) {
+        self.steps += 1;
+        *self.iterations.entry(var.clone()).or_insert(0) += 1;
+    }
+
+    /// Records an evaluation of a modality.
+    pub fn modality(&mut self) {
+        *self.modalities.get_or_insert(0) += 1;
+    }
+
+    /// Records an evaluation of `f` that took `time` and resulted in a set
+    /// of `states` states, if `f` is one of the recorded subformulas.
+    pub fn evaluated(
+        &mut self,
+        f: &mc::Formula,
+        states: usize,
+        time: Duration,
+    ) {
+        let subformulas = match &mut self.subformulas {
+            Some(subformulas) => subformulas,
+            None => return,
+        };
+        if let Some(&i) = subformulas.index.get(&(f as *const _ as usize)) {
+            let entry = subformulas.evaluations.entry(i).or_default();
+            entry.evaluations += 1;
+            entry.states = states;
+            entry.time += time;
+        }
+    }
+
+    /// The evaluations of every subformula, if they are recorded.
+    pub fn subformulas(&self) -> Option<Vec<Subformula>> {
+        self.subformulas.as_ref().map(Subformulas::named)
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.to_string(),
+            Format::Json => serde_json::to_string_pretty(self).unwrap(),
+        }
+    }
+}
+
+impl Subformulas {
+    fn named(&self) -> Vec<Subformula> {
+        self.formula
+            .subformulas()
+            .enumerate()
+            .map(|(i, g)| Subformula {
+                formula: g.to_string(),
+                ..self.evaluations.get(&i).cloned().unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+fn named<S: Serializer>(
+    subformulas: &Option<Subformulas>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    subformulas.as_ref().map(Subformulas::named).serialize(serializer)
+}
+
+fn seconds<S: Serializer>(
+    time: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(time.as_secs_f64())
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("unknown statistics format {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "wall time: {:.3?}", self.time)?;
+        writeln!(fmt, "steps: {}", self.steps)?;
+        if let Some(n) = self.modalities {
+            writeln!(fmt, "modality evaluations: {}", n)?;
+        }
+        writeln!(fmt, "cache hits: {}", self.cache_hits)?;
+        if !self.iterations.is_empty() {
+            writeln!(fmt, "iterations per fixpoint:")?;
+            for (var, n) in &self.iterations {
+                writeln!(fmt, "  {:<8} {}", var, n)?;
+            }
+        }
+        if let Some(subformulas) = self.subformulas() {
+            writeln!(fmt, "evaluations     states       time  subformula")?;
+            for g in &subformulas {
+                writeln!(
+                    fmt,
+                    "{:>11} {:>10} {:>10.3?}  {}",
+                    g.evaluations, g.states, g.time, g.formula
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subformulas() {
+        let f = "nu X. (<a>X && <a>X)".parse::<mc::Formula>().unwrap();
+        let f = Arc::new(f);
+        let mut stats = Stats::with_subformulas(f.clone());
+        let ms = Duration::from_millis(1);
+        stats.evaluated(&f, 3, ms);
+        stats.evaluated(&f, 2, ms);
+        let subformulas = stats.subformulas().unwrap();
+        assert_eq!(subformulas.len(), f.subformulas().count());
+        assert_eq!(subformulas[0].formula, f.to_string());
+        assert_eq!(subformulas[0].evaluations, 2);
+        assert_eq!(subformulas[0].states, 2);
+        assert_eq!(subformulas[0].time, 2 * ms);
+        // Equal subformulas at different positions are kept apart.
+        assert!(subformulas[1..].iter().all(|g| g.evaluations == 0));
+
+        stats.iteration(&"X".to_string());
+        stats.iteration(&"X".to_string());
+        assert_eq!(stats.steps, 2);
+        assert_eq!(stats.iterations["X"], 2);
+        let json = serde_json::from_str::<serde_json::Value>(
+            &stats.render(Format::Json),
+        )
+        .unwrap();
+        assert_eq!(json["subformulas"][0]["time"], 0.002);
+        assert_eq!(json["modalities"], 0);
+    }
+
+    #[test]
+    fn branches() {
+        let f = "<a>true && <b>true".parse::<mc::Formula>().unwrap();
+        let f = Arc::new(f);
+        let (f1, f2) = match &*f {
+            mc::Formula::And { f1, f2 } => (&**f1, &**f2),
+            _ => unreachable!(),
+        };
+        let mut stats = Stats::with_subformulas(f.clone());
+        let mut branch = stats.branch();
+        let ms = Duration::from_millis(1);
+        stats.modality();
+        stats.evaluated(f1, 1, ms);
+        branch.modality();
+        branch.iteration(&"X".to_string());
+        branch.evaluated(f2, 2, ms);
+        stats.merge(branch);
+        assert_eq!(stats.modalities, Some(2));
+        assert_eq!(stats.iterations["X"], 1);
+        for g in stats.subformulas().unwrap() {
+            let expected = match g.formula.as_str() {
+                "<a>true" => (1, 1),
+                "<b>true" => (1, 2),
+                _ => (0, 0),
+            };
+            assert_eq!((g.evaluations, g.states), expected);
+        }
+    }
+
+    #[test]
+    fn not_applicable() {
+        let json = serde_json::from_str::<serde_json::Value>(
+            &Stats::new().render(Format::Json),
+        )
+        .unwrap();
+        assert!(json.get("modalities").is_none());
+        assert!(json.get("subformulas").is_none());
+        assert!(!Stats::new().to_string().contains("modality"));
+    }
+}
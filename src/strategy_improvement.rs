@@ -0,0 +1,281 @@
+use crate::{
+    checker::{ModelChecker, Options},
+    game::{Game, Player},
+    linear,
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+};
+use std::collections::VecDeque;
+
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let game = Game::new(lts, f, valuation);
+    let mut stats = Stats::new();
+    let even = solve(&game, &mut stats);
+    let result =
+        lts.states().iter().filter(|&s| even[game.vertex(s)]).collect();
+    (result, stats)
+}
+
+pub struct StrategyImprovement;
+
+impl ModelChecker for StrategyImprovement {
+    fn name(&self) -> &'static str {
+        "strategy-improvement"
+    }
+
+    fn description(&self) -> &'static str {
+        "Solves the parity game by strategy improvement"
+    }
+
+    fn steps(&self) -> &'static str {
+        "strategy improvement steps"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+/// The value of a vertex for a strategy of `Even`, when `Odd` responds as
+/// well as it can.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Value {
+    /// `Odd` can force a cycle with an odd highest priority.
+    Lost,
+    /// `Odd` can force the play to retreat; for every priority from the
+    /// highest down, how often the play visits it on the way, counted
+    /// negatively for odd priorities.
+    Retreat(Vec<i32>),
+    /// `Odd` can only avoid a retreat by a cycle with an even highest
+    /// priority.
+    Won,
+}
+
+/// A strategy of `Even`, where `None` retreats from the game.
+type Strategy = Vec<Option<usize>>;
+
+/// Solves a parity game by strategy improvement in the style of Björklund
+/// and Vorobyov, returning for every vertex whether `Even` wins it. `Even`
+/// may also retreat from any of its vertices, which it does everywhere in
+/// the initial strategy. Every iteration evaluates the strategy against the
+/// best response of `Odd`, and then switches every vertex of `Even` to a
+/// successor of strictly higher value, until no such successor is left.
+pub fn solve(game: &Game, stats: &mut Stats) -> Vec<bool> {
+    let retreat = Value::Retreat(vec![0; width(game)]);
+    let mut strategy = vec![None; game.vertices.len()];
+    loop {
+        stats.steps += 1;
+        let values = evaluate(game, &strategy);
+        let mut improved = false;
+        let value = |w: Option<usize>| match w {
+            Some(w) => &values[w],
+            None => &retreat,
+        };
+        for (v, choice) in strategy.iter_mut().enumerate() {
+            if game.owner[v] == Player::Odd {
+                continue;
+            }
+            let best = std::iter::once(None)
+                .chain(game.succ[v].iter().map(|&w| Some(w)))
+                .fold(*choice, |best, w| match value(w) > value(best) {
+                    true => w,
+                    false => best,
+                });
+            if value(best) > value(*choice) {
+                *choice = best;
+                improved = true;
+            }
+        }
+        if !improved {
+            break values
+                .into_iter()
+                .map(|value| value == Value::Won)
+                .collect();
+        }
+    }
+}
+
+/// The number of priorities, i.e. the length of the vectors of `Retreat`.
+fn width(game: &Game) -> usize {
+    game.priority.iter().max().map_or(0, |&p| p as usize + 1)
+}
+
+/// Computes the values of all vertices for `strategy`.
+fn evaluate(game: &Game, strategy: &Strategy) -> Vec<Value> {
+    let n = game.vertices.len();
+    let width = width(game);
+    let succ = (0..n)
+        .map(|v| match game.owner[v] {
+            Player::Even => strategy[v].into_iter().collect(),
+            Player::Odd => game.succ[v].clone(),
+        })
+        .collect::<Vec<Vec<usize>>>();
+    let mut pred = vec![Vec::new(); n];
+    for (v, ws) in succ.iter().enumerate() {
+        for &w in ws {
+            pred[w].push(v);
+        }
+    }
+
+    // The vertices on a cycle with an odd highest priority, and then those
+    // from which `Odd` can reach one.
+    let mut lost = vec![false; n];
+    let mut odd = game
+        .priority
+        .iter()
+        .cloned()
+        .filter(|&p| Player::of_priority(p) == Player::Odd)
+        .collect::<Vec<_>>();
+    odd.sort();
+    odd.dedup();
+    for p in odd {
+        let below = (0..n)
+            .map(|v| match game.priority[v] <= p {
+                true => succ[v]
+                    .iter()
+                    .cloned()
+                    .filter(|&w| game.priority[w] <= p)
+                    .collect(),
+                false => Vec::new(),
+            })
+            .collect::<Vec<Vec<usize>>>();
+        for component in linear::components(&below) {
+            let cycle = component.len() > 1
+                || below[component[0]].contains(&component[0]);
+            if cycle && component.iter().any(|&v| game.priority[v] == p) {
+                for v in component {
+                    lost[v] = true;
+                }
+            }
+        }
+    }
+    let origins = (0..n).filter(|&v| lost[v]).collect::<Vec<_>>();
+    for v in reach(&pred, origins) {
+        lost[v] = true;
+    }
+
+    // The shortest retreats, where `Odd` minimises. Plays that cannot
+    // retreat end in a cycle with an even highest priority.
+    let mut distance = vec![None; n];
+    let mut queue = VecDeque::new();
+    for v in 0..n {
+        if game.owner[v] == Player::Even && strategy[v].is_none() && !lost[v] {
+            distance[v] = Some(weight(game, v, &vec![0; width]));
+            queue.push_back(v);
+        }
+    }
+    while let Some(w) = queue.pop_front() {
+        for &v in &pred[w] {
+            if lost[v] {
+                continue;
+            }
+            let candidate = weight(game, v, distance[w].as_ref().unwrap());
+            let shorter = match &distance[v] {
+                Some(d) => candidate < *d,
+                None => true,
+            };
+            if shorter {
+                distance[v] = Some(candidate);
+                queue.push_back(v);
+            }
+        }
+    }
+
+    (0..n)
+        .map(|v| match (lost[v], distance[v].take()) {
+            (true, _) => Value::Lost,
+            (false, Some(d)) => Value::Retreat(d),
+            (false, None) => Value::Won,
+        })
+        .collect()
+}
+
+/// Adds the priority of `v` to the visits of `rest`.
+fn weight(game: &Game, v: usize, rest: &[i32]) -> Vec<i32> {
+    let p = game.priority[v];
+    let mut visits = rest.to_vec();
+    let i = visits.len() - 1 - p as usize;
+    match Player::of_priority(p) {
+        Player::Even => visits[i] += 1,
+        Player::Odd => visits[i] -= 1,
+    }
+    visits
+}
+
+/// The vertices from which `origins` can be reached, given the predecessors
+/// of every vertex.
+fn reach(pred: &[Vec<usize>], origins: Vec<usize>) -> Vec<usize> {
+    let mut seen = vec![false; pred.len()];
+    for &v in &origins {
+        seen[v] = true;
+    }
+    let mut stack = origins.clone();
+    let mut reached = origins;
+    while let Some(w) = stack.pop() {
+        for &v in &pred[w] {
+            if !seen[v] {
+                seen[v] = true;
+                stack.push(v);
+                reached.push(v);
+            }
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let retreat = |visits: &[i32]| Value::Retreat(visits.to_vec());
+        assert!(Value::Lost < retreat(&[-5, 0]));
+        assert!(retreat(&[5, 0]) < Value::Won);
+        // Visits of higher priorities take precedence, and odd ones count
+        // against `Even`.
+        assert!(retreat(&[0, 5]) < retreat(&[1, 0]));
+        assert!(retreat(&[-1, 5]) < retreat(&[0, 0]));
+    }
+
+    /// A game on vertices with the given owners, priorities and successors.
+    fn game(vertices: &[(Player, u32, Vec<usize>)]) -> Game {
+        let mut game = Game::default();
+        for (v, (owner, priority, succ)) in vertices.iter().enumerate() {
+            game.vertices.push((v as lts::State, 0));
+            game.owner.push(*owner);
+            game.priority.push(*priority);
+            game.succ.push(succ.clone());
+        }
+        game
+    }
+
+    #[test]
+    fn steps() {
+        // Both vertices of `Even` switch from retreating to the loop in the
+        // first step; the second step finds nothing left to improve.
+        let chain =
+            game(&[(Player::Even, 0, vec![1]), (Player::Even, 2, vec![1])]);
+        let mut stats = Stats::new();
+        assert_eq!(solve(&chain, &mut stats), [true, true]);
+        assert_eq!(stats.steps, 2);
+
+        // `Even` has nothing to choose, so the first strategy is final.
+        let lost = game(&[(Player::Odd, 1, vec![0])]);
+        let mut stats = Stats::new();
+        assert_eq!(solve(&lost, &mut stats), [false]);
+        assert_eq!(stats.steps, 1);
+    }
+}
+
+generate_tests!();
@@ -0,0 +1,247 @@
+use crate::{
+    bdd::{self, Bdd, Manager},
+    checker::{ModelChecker, Options},
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+};
+use std::collections::HashMap;
+
+/// Evaluates `f` with the Emerson-Lei algorithm on sets of states encoded as
+/// BDDs. States are encoded by the bits of their numbers, and the modalities
+/// of every action formula by the relation of the transitions it matches.
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let f = f.to_pnf().lower_regular().rename_bound();
+    let mut symbolic = Encoding::new(lts);
+    let mut env = valuation
+        .iter()
+        .map(|(var, states)| (var.clone(), symbolic.encode(states)))
+        .collect::<Env>();
+    for g in f.subformulas() {
+        match g {
+            mc::Formula::Mu { var, .. } => {
+                env.insert(var.clone(), bdd::FALSE);
+            },
+            mc::Formula::Nu { var, .. } => {
+                env.insert(var.clone(), symbolic.states);
+            },
+            _ => (),
+        }
+    }
+    let sat = symbolic.eval(&f, None, &mut env);
+    (symbolic.decode(sat), symbolic.stats)
+}
+
+pub struct Symbolic;
+
+impl ModelChecker for Symbolic {
+    fn name(&self) -> &'static str {
+        "symbolic"
+    }
+
+    fn description(&self) -> &'static str {
+        "Runs the Emerson-Lei algorithm on sets of states encoded as BDDs"
+    }
+
+    fn steps(&self) -> &'static str {
+        "fixpoint iterations"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+type Env = HashMap<mc::VarName, Bdd>;
+
+struct Encoding<'a> {
+    lts: &'a Lts,
+    manager: Manager,
+    /// The variables encoding a state, most significant bit first.
+    vars: Vec<u32>,
+    states: Bdd,
+    relations: HashMap<*const mc::ActionFormula, Bdd>,
+    stats: Stats,
+}
+
+impl<'a> Encoding<'a> {
+    fn new(lts: &'a Lts) -> Encoding<'a> {
+        let max = lts.states().iter().last().unwrap_or(0);
+        let bits = (32 - max.leading_zeros()).max(1);
+        let mut symbolic = Encoding {
+            lts,
+            manager: Manager::new(),
+            vars: (0..bits).map(|i| 2 * i).collect(),
+            states: bdd::FALSE,
+            relations: HashMap::new(),
+            stats: Stats::with_modalities(),
+        };
+        symbolic.states = symbolic.encode(lts.states());
+        symbolic
+    }
+
+    fn encode(&mut self, states: &lts::StateSet) -> Bdd {
+        let keys = states.iter().map(u64::from).collect::<Vec<_>>();
+        self.manager.of_keys(&keys, &self.vars)
+    }
+
+    fn decode(&self, sat: Bdd) -> lts::StateSet {
+        let keys = self.manager.keys(sat, &self.vars);
+        keys.into_iter().map(|k| k as lts::State).collect()
+    }
+
+    fn eval(
+        &mut self,
+        f: &mc::Formula,
+        prev_fixpoint: Option<&mc::Formula>,
+        env: &mut Env,
+    ) -> Bdd {
+        use mc::Formula::*;
+        match f {
+            Var { name } => env[name],
+            True => self.states,
+            False => bdd::FALSE,
+            Not { f: g } => {
+                let sat = self.eval(g, prev_fixpoint, env);
+                self.complement(sat)
+            },
+            And { f1, f2 } => {
+                let sat1 = self.eval(f1, prev_fixpoint, env);
+                let sat2 = self.eval(f2, prev_fixpoint, env);
+                self.manager.and(sat1, sat2)
+            },
+            Or { f1, f2 } => {
+                let sat1 = self.eval(f1, prev_fixpoint, env);
+                let sat2 = self.eval(f2, prev_fixpoint, env);
+                self.manager.or(sat1, sat2)
+            },
+            Implies { .. } | Iff { .. } =>
+                unreachable!("formula is not in positive normal form"),
+            Diamond { step, f: g } => {
+                let sat = self.eval(g, prev_fixpoint, env);
+                self.possibly(step, sat)
+            },
+            Box { step, f: g } => {
+                // [a]g is equivalent to !<a>!g.
+                let sat = self.eval(g, prev_fixpoint, env);
+                let outside = self.complement(sat);
+                let escape = self.possibly(step, outside);
+                self.complement(escape)
+            },
+            Mu { var, f: g } | Nu { var, f: g } => {
+                match (f, prev_fixpoint) {
+                    (Mu { .. }, Some(Nu { .. }))
+                    | (Nu { .. }, Some(Mu { .. })) =>
+                        self.reset_fixpoints(f, env),
+                    _ => (),
+                }
+                loop {
+                    self.stats.iteration(var);
+                    let new = self.eval(g, Some(f), env);
+                    let prev = env.insert(var.clone(), new).unwrap();
+                    if prev == new {
+                        break new;
+                    }
+                }
+            },
+        }
+    }
+
+    fn complement(&mut self, sat: Bdd) -> Bdd {
+        let outside = self.manager.not(sat);
+        self.manager.and(self.states, outside)
+    }
+
+    /// The states with a `step`-successor in `sat`, as the relational
+    /// product of the transitions of `step` and `sat`.
+    fn possibly(&mut self, step: &mc::RegFormula, sat: Bdd) -> Bdd {
+        self.stats.modality();
+        let act = match step {
+            mc::RegFormula::Action { act } => act,
+            _ => unreachable!("regular formulas should be lowered"),
+        };
+        let relation = self.relation(act);
+        let successors = self.manager.prime(sat);
+        self.manager.and_exists(relation, successors)
+    }
+
+    /// The transitions with a label matching `act`, over the variables of a
+    /// state and its successor.
+    fn relation(&mut self, act: &mc::ActionFormula) -> Bdd {
+        if let Some(&relation) = self.relations.get(&(act as *const _)) {
+            return relation;
+        }
+        let bits = self.vars.len() as u32;
+        let mut keys = Vec::new();
+        for l in self.lts.labels_matching(|label| act.matches(label)) {
+            for (s, ts) in self.lts.transitions(l) {
+                keys.extend(
+                    ts.iter()
+                        .map(|&t| bdd::interleave(s.into(), t.into(), bits)),
+                );
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        let vars = (0..2 * bits).collect::<Vec<_>>();
+        let relation = self.manager.of_keys(&keys, &vars);
+        self.relations.insert(act, relation);
+        relation
+    }
+
+    fn reset_fixpoints(&self, f: &mc::Formula, env: &mut Env) {
+        for var in f.open_fixpoints() {
+            let value = match f.is_mu() {
+                true => bdd::FALSE,
+                false => self.states,
+            };
+            env.insert(var.clone(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naive;
+
+    #[test]
+    fn explicit() {
+        let lts = "des (0,9,6)\n(0,\"a\",1)\n(0,\"b\",2)\n(1,\"a\",3)\n\
+                   (2,\"tau\",2)\n(3,\"b\",0)\n(3,\"a\",4)\n(4,\"tau\",5)\n\
+                   (5,\"a\",5)\n(5,\"b\",1)"
+            .parse::<Lts>()
+            .unwrap();
+        let formulas = [
+            "<a>true",
+            "[b]false",
+            "[a.a]<b>true",
+            "nu X. <a>X",
+            "mu X. ([a]X && <true>true)",
+            "nu X. mu Y. (<a>X || <b|tau>Y)",
+            "mu X. nu Y. ([a]X && [!a]Y)",
+            "<true*.tau>true",
+        ];
+        for formula in &formulas {
+            let f = formula.parse::<mc::Formula>().unwrap();
+            let valuation = mc::Valuation::new();
+            assert_eq!(
+                eval(&lts, &f, &valuation).0,
+                naive::eval(&lts, &f, &valuation).0,
+                "{}",
+                formula
+            );
+        }
+    }
+}
+
+generate_tests!();
@@ -0,0 +1,327 @@
+use crate::{
+    checker::{ModelChecker, Options},
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    rc::Rc,
+};
+
+/// Decides whether the initial state satisfies `f` by building a tableau
+/// from it. States are only visited when a modality steps to them, and the
+/// search stops as soon as the verdict is known. The result contains at most
+/// the initial state.
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let f = f.to_pnf().lower_regular().rename_bound();
+    let mut tableau = Builder {
+        lts,
+        valuation,
+        free: f.free_variables(),
+        closed: HashMap::new(),
+        cache: HashMap::new(),
+        instances: 0,
+        unfolded: HashSet::new(),
+        stats: Stats::with_modalities(),
+    };
+    let result = match tableau.check(lts.init(), &f) {
+        true => std::iter::once(lts.init()).collect(),
+        false => lts::StateSet::new(),
+    };
+    (result, tableau.stats)
+}
+
+pub struct Tableau;
+
+impl ModelChecker for Tableau {
+    fn name(&self) -> &'static str {
+        "tableau"
+    }
+
+    fn description(&self) -> &'static str {
+        "Builds a tableau for the initial state only"
+    }
+
+    fn steps(&self) -> &'static str {
+        "fixpoint unfoldings"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+}
+
+/// The fixpoints a formula is nested in, innermost first. Every fixpoint is
+/// tagged with the number of the instance it belongs to; a fixpoint starts
+/// a new instance every time it is entered, rather than unfolded.
+type Scope<'a> = Option<Rc<Binding<'a>>>;
+
+struct Binding<'a> {
+    var: &'a mc::VarName,
+    fixpoint: &'a mc::Formula,
+    instance: usize,
+    parent: Scope<'a>,
+}
+
+struct Builder<'a> {
+    lts: &'a Lts,
+    valuation: &'a mc::Valuation,
+    /// The free variables of the whole formula.
+    free: BTreeSet<mc::VarName>,
+    /// Whether a subformula contains no bound variables of its context.
+    closed: HashMap<*const mc::Formula, bool>,
+    /// Verdicts for closed subformulas, which do not depend on the tags.
+    cache: HashMap<(lts::State, *const mc::Formula), bool>,
+    /// The number of fixpoint instances entered so far.
+    instances: usize,
+    /// The tags: the states in which every fixpoint instance has been
+    /// unfolded on the current branch of the tableau.
+    unfolded: HashSet<(usize, lts::State)>,
+    /// Counts the unfoldings of every fixpoint.
+    stats: Stats,
+}
+
+/// What remains to be done for a node of the tableau whose child is being
+/// checked. The branch being built is a stack of these, so that its length
+/// is not limited by the call stack.
+enum Frame<'a> {
+    Not,
+    /// The first operand of a conjunction (if `all`) or disjunction has
+    /// been checked; unless that decides it, `f` is checked next.
+    Second {
+        all: bool,
+        s: lts::State,
+        f: &'a mc::Formula,
+        scope: Scope<'a>,
+    },
+    /// The targets of a box (if `all`) or diamond modality that remain to be
+    /// checked against `f`.
+    Targets {
+        all: bool,
+        targets: std::vec::IntoIter<lts::State>,
+        f: &'a mc::Formula,
+        scope: Scope<'a>,
+    },
+    /// Removes the tag of an unfolding when the branch backtracks over it.
+    Unfolded((usize, lts::State)),
+    /// Stores the verdict for a closed fixpoint.
+    Cache((lts::State, *const mc::Formula)),
+}
+
+/// The next step in building the tableau.
+enum Next<'a> {
+    Check(lts::State, &'a mc::Formula, Scope<'a>),
+    Verdict(bool),
+}
+
+impl<'a> Builder<'a> {
+    fn check(&mut self, s: lts::State, f: &'a mc::Formula) -> bool {
+        let mut branch = Vec::new();
+        let mut next = Next::Check(s, f, None);
+        loop {
+            let mut holds = match next {
+                Next::Check(s, f, scope) => {
+                    next = self.expand(s, f, scope, &mut branch);
+                    continue;
+                },
+                Next::Verdict(holds) => holds,
+            };
+            // Backtrack until a node needs another child to be checked.
+            next = loop {
+                let frame = match branch.pop() {
+                    Some(frame) => frame,
+                    None => return holds,
+                };
+                match frame {
+                    Frame::Not => holds = !holds,
+                    Frame::Second { all, s, f, scope } if holds == all =>
+                        break Next::Check(s, f, scope),
+                    Frame::Second { .. } => (),
+                    Frame::Targets { all, mut targets, f, scope }
+                        if holds == all =>
+                        if let Some(t) = targets.next() {
+                            let next = Next::Check(t, f, scope.clone());
+                            branch.push(Frame::Targets {
+                                all,
+                                targets,
+                                f,
+                                scope,
+                            });
+                            break next;
+                        },
+                    Frame::Targets { .. } => (),
+                    Frame::Unfolded(tag) => {
+                        self.unfolded.remove(&tag);
+                    },
+                    Frame::Cache(key) => {
+                        self.cache.insert(key, holds);
+                    },
+                }
+            };
+        }
+    }
+
+    /// Checks `f` in state `s` as far as possible without checking a
+    /// subformula, pushing what remains onto `branch`.
+    fn expand(
+        &mut self,
+        s: lts::State,
+        f: &'a mc::Formula,
+        scope: Scope<'a>,
+        branch: &mut Vec<Frame<'a>>,
+    ) -> Next<'a> {
+        use mc::Formula::*;
+        match f {
+            True => Next::Verdict(true),
+            False => Next::Verdict(false),
+            Var { name } => match lookup(&scope, name) {
+                Some(binding) => self.unfold(
+                    s,
+                    binding.fixpoint,
+                    binding.instance,
+                    binding.parent.clone(),
+                    branch,
+                ),
+                None => Next::Verdict(self.valuation[name].contains(&s)),
+            },
+            Not { f } => {
+                branch.push(Frame::Not);
+                Next::Check(s, f, scope)
+            },
+            And { f1, f2 } | Or { f1, f2 } => {
+                let all = matches!(f, And { .. });
+                let second =
+                    Frame::Second { all, s, f: f2, scope: scope.clone() };
+                branch.push(second);
+                Next::Check(s, f1, scope)
+            },
+            Diamond { step, f: g } | Box { step, f: g } => {
+                self.stats.modality();
+                let all = matches!(f, Box { .. });
+                let mut targets = self.targets(s, step).into_iter();
+                match targets.next() {
+                    Some(t) => {
+                        let next = Next::Check(t, g, scope.clone());
+                        branch.push(Frame::Targets {
+                            all,
+                            targets,
+                            f: g,
+                            scope,
+                        });
+                        next
+                    },
+                    None => Next::Verdict(all),
+                }
+            },
+            Mu { .. } | Nu { .. } => {
+                let key = (s, f as *const _);
+                if let Some(&holds) = self.cache.get(&key) {
+                    return Next::Verdict(holds);
+                }
+                if self.is_closed(f) {
+                    branch.push(Frame::Cache(key));
+                }
+                self.instances += 1;
+                self.unfold(s, f, self.instances, scope, branch)
+            },
+            Implies { .. } | Iff { .. } =>
+                unreachable!("formula is not in positive normal form"),
+        }
+    }
+
+    /// Unfolds `fixpoint` in state `s`, unless this instance has been
+    /// unfolded there before on this branch. Such a repetition is
+    /// successful exactly for greatest fixpoints.
+    fn unfold(
+        &mut self,
+        s: lts::State,
+        fixpoint: &'a mc::Formula,
+        instance: usize,
+        scope: Scope<'a>,
+        branch: &mut Vec<Frame<'a>>,
+    ) -> Next<'a> {
+        let (var, body) = match fixpoint {
+            mc::Formula::Mu { var, f } | mc::Formula::Nu { var, f } =>
+                (var, &**f),
+            _ => unreachable!("only fixpoints can be unfolded"),
+        };
+        if !self.unfolded.insert((instance, s)) {
+            return Next::Verdict(fixpoint.is_nu());
+        }
+        self.stats.iteration(var);
+        branch.push(Frame::Unfolded((instance, s)));
+
+        let binding = Binding { var, fixpoint, instance, parent: scope };
+        Next::Check(s, body, Some(Rc::new(binding)))
+    }
+
+    fn targets(&self, s: lts::State, step: &mc::RegFormula) -> Vec<lts::State> {
+        let act = match step {
+            mc::RegFormula::Action { act } => act,
+            _ => unreachable!("regular formulas should be lowered"),
+        };
+        self.lts
+            .successors(s)
+            .filter(|(l, _)| act.matches(l))
+            .map(|(_, t)| t)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn is_closed(&mut self, f: &mc::Formula) -> bool {
+        let free = &self.free;
+        *self
+            .closed
+            .entry(f as *const _)
+            .or_insert_with(|| f.free_variables().is_subset(free))
+    }
+}
+
+fn lookup<'a, 'b>(
+    scope: &'b Scope<'a>,
+    name: &mc::VarName,
+) -> Option<&'b Binding<'a>> {
+    let mut scope = scope.as_ref();
+    while let Some(binding) = scope {
+        if binding.var == name {
+            return Some(binding);
+        }
+        scope = binding.parent.as_ref();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_chain() {
+        let n = 100_000;
+        let mut lts = format!("des (0,{},{})\n", n - 1, n);
+        for s in 0..n - 1 {
+            lts.push_str(&format!("({},\"a\",{})\n", s, s + 1));
+        }
+        let lts = lts.parse::<Lts>().unwrap();
+        // Every branch of the tableau follows the whole chain.
+        let f = "mu X. [a]X".parse::<mc::Formula>().unwrap();
+        let (result, stats) = eval(&lts, &f, &mc::Valuation::new());
+        assert!(result.contains(&lts.init()));
+        assert_eq!(stats.steps, n as u64);
+        let f = "nu X. <a>X".parse::<mc::Formula>().unwrap();
+        assert!(eval(&lts, &f, &mc::Valuation::new()).0.is_empty());
+    }
+}
+
+generate_tests!();
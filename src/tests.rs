@@ -20,11 +20,43 @@ macro_rules! generate_tests {
 (6,"tau",2)
 (7,"b",1)"#;
 
+            const PHILOSOPHERS: &str = r#"des (0,12,10)
+(0,"lock(p2, f2)",1)
+(0,"lock(p1, f1)",2)
+(1,"lock(p1, f1)",3)
+(1,"lock(p2, f1)",4)
+(2,"lock(p2, f2)",3)
+(2,"lock(p1, f2)",5)
+(4,"eat(p2)",6)
+(5,"eat(p1)",7)
+(6,"free(p2, f2)",8)
+(7,"free(p1, f1)",9)
+(8,"free(p2, f1)",0)
+(9,"free(p1, f2)",0)"#;
+
             fn check_formula(formula: &str, expected: bool) {
-                let lts = LTS.parse::<Lts>().unwrap();
+                check_formula_on(LTS, formula, expected)
+            }
+
+            fn check_formula_on(lts: &str, formula: &str, expected: bool) {
+                check_formula_with(
+                    lts,
+                    formula,
+                    &mc::Valuation::new(),
+                    expected,
+                )
+            }
+
+            fn check_formula_with(
+                lts: &str,
+                formula: &str,
+                valuation: &mc::Valuation,
+                expected: bool,
+            ) {
+                let lts = lts.parse::<Lts>().unwrap();
                 let f = formula.parse::<mc::Formula>().unwrap();
                 dbg!(formula);
-                let result = eval(&lts, &f);
+                let (result, _) = eval(&lts, &f, valuation);
                 dbg!(&result);
                 assert_eq!(result.contains(&0), expected)
             }
@@ -51,6 +83,68 @@ macro_rules! generate_tests {
                 check_formula("<tau>false", false);
             }
 
+            #[test]
+            fn negation() {
+                check_formula("!false", true);
+                check_formula("!<a>true", true);
+                check_formula("not <tau>true", false);
+                check_formula("!<tau>![tau]false", false);
+                check_formula("!nu X. <tau>X", false);
+                check_formula("nu X. !mu Y. !<tau>X", true);
+            }
+
+            #[test]
+            fn implication() {
+                check_formula("<tau>true => <a>true", false);
+                check_formula("<a>true => false", true);
+                check_formula("<a>true <=> <b>true", true);
+                check_formula("<tau>[tau]false <=> <tau>true", false);
+                check_formula("nu X. (<a>true => [b]X)", true);
+                check_formula("mu X. (!X => <tau>X)", false);
+                check_formula(
+                    "nu X. ([tau]X && (<a>true => <tau>true))",
+                    false,
+                );
+            }
+
+            #[test]
+            fn regular_modalities() {
+                check_formula("<tau*.a>true", true);
+                check_formula("<tau.a>true", false);
+                check_formula("[a+b]false", true);
+                check_formula("<(tau.tau)*.b>true", true);
+                check_formula("<tau+>[tau]false", true);
+                check_formula("[tau*]<tau>true", false);
+                check_formula("[tau*.(a+b)](<tau>true || <b>true)", true);
+            }
+
+            #[test]
+            fn action_formulas() {
+                check_formula("nu X. (<true>true && [true]X)", true);
+                check_formula("<!tau>true", false);
+                check_formula("[!tau]false", true);
+                check_formula("<a|b>true", false);
+                check_formula("<tau*.(a|b)>true", true);
+                check_formula("<tau.!tau>true", false);
+                check_formula("<tau.tau.!(tau|b)>true", true);
+                check_formula("[true*.a&b]false", true);
+            }
+
+            #[test]
+            fn action_labels() {
+                let check =
+                    |f, expected| check_formula_on(PHILOSOPHERS, f, expected);
+                check(r#"<"lock(p1, f1)">true"#, true);
+                check("<lock(_, f1)>true", true);
+                check("<lock(p2, _)>true", true);
+                check("<lock(_, f3)>true", false);
+                check("<lock(_, _)*.eat(p1)>true", true);
+                check("<lock(p2, f2).lock(p2,f1).eat(_)>true", true);
+                check("<true*.eat>true", false);
+                check("[true*]<true>true", false);
+                check("[true*.eat(p1)]<free(p1, _)>true", true);
+            }
+
             #[test]
             fn fixpoints() {
                 check_formula("nu X. X", true);
@@ -58,6 +152,42 @@ macro_rules! generate_tests {
                 check_formula("nu X. mu Y. (X || Y)", true);
                 check_formula("nu X. mu Y. (X && Y)", false);
                 check_formula("nu X. (X && mu Y. Y)", false);
+                check_formula("nu Inf. <tau>Inf", true);
+                check_formula("mu X'. ([tau]X' && <tau>true)", false);
+                check_formula("nu X1. mu X2. (<tau>X2 || <a>X1)", true);
+            }
+
+            #[test]
+            fn shadowing() {
+                check_formula("mu X. (X || nu X. X)", true);
+                check_formula("nu X. (X && mu X. X)", false);
+                check_formula("mu X. (nu X. <tau>X || <b>X)", true);
+                check_formula(
+                    "nu X. (<tau>X && mu X. (<a>true || <tau>X))",
+                    true,
+                );
+                check_formula(
+                    "mu X. ([tau]X && nu X. (<tau>X || <b>X))",
+                    false,
+                );
+                check_formula("nu X. mu X. nu X. (<a>X || <tau>X)", true);
+            }
+
+            #[test]
+            fn open_formulas() {
+                let check = |f, x: &[lts::State], expected| {
+                    let mut valuation = mc::Valuation::new();
+                    valuation
+                        .insert("X".to_string(), x.iter().cloned().collect());
+                    check_formula_with(LTS, f, &valuation, expected)
+                };
+                check("X", &[0], true);
+                check("!X", &[0], false);
+                check("<tau>X", &[1], true);
+                check("<tau>X", &[3], false);
+                check("mu Y. (X || <tau>Y)", &[7], true);
+                check("nu Y. (!X && [tau]Y)", &[7], false);
+                check("nu Y. (!X && <tau>Y)", &[7], true);
             }
 
             #[test]
@@ -0,0 +1,593 @@
+use crate::{
+    game::{Game, Player, Solution},
+    lts::{self, Lts},
+    mu_calculus as mc, naive,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fmt::{self, Write},
+    rc::Rc,
+    str::FromStr,
+};
+
+/// Evidence for the verdict on the initial state: a graph of obligations
+/// `state ⊨ subformula`, each of which is discharged by its successors. For a
+/// negative verdict the evidence is for the negation of the formula, i.e. it
+/// is a counterexample. Cycles only pass through greatest fixpoints.
+#[derive(Clone, Debug, Serialize)]
+pub struct Witness {
+    pub holds: bool,
+    pub formula: String,
+    pub nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Node {
+    pub state: lts::State,
+    pub formula: String,
+    pub edges: Vec<Edge>,
+}
+
+/// Points to the obligation discharging (part of) its source. The label is
+/// present when the edge follows a transition of the LTS.
+#[derive(Clone, Debug, Serialize)]
+pub struct Edge {
+    pub label: Option<lts::Label>,
+    pub target: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Dot,
+}
+
+/// A fixpoint entered while constructing evidence, for a given scope. Least
+/// fixpoints record the rank of every state in them: the index of the first
+/// approximant containing it, where approximant 0 is empty.
+struct Instance<'a> {
+    var: &'a mc::VarName,
+    body: &'a mc::Formula,
+    value: Value,
+}
+
+enum Value {
+    Least(HashMap<lts::State, usize>),
+    Greatest(Rc<lts::StateSet>),
+}
+
+/// A bound variable: an instance of its fixpoint and, for a least fixpoint,
+/// the rank of the approximant it is bound to, which decreases every time
+/// the variable is unfolded.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Bound {
+    instance: usize,
+    rank: usize,
+}
+
+type Scope = Vec<Bound>;
+type Key = (lts::State, usize, Scope);
+
+struct Builder<'a> {
+    lts: &'a Lts,
+    valuation: &'a mc::Valuation,
+    nodes: Vec<Node>,
+    visited: HashMap<Key, usize>,
+    queue: VecDeque<(usize, &'a mc::Formula, Scope)>,
+    instances: Vec<Instance<'a>>,
+    /// The instance of every fixpoint in every scope, keyed by the address
+    /// of the fixpoint.
+    entered: HashMap<(usize, Scope), usize>,
+    /// The states satisfying a fixpoint subformula, keyed by its address and
+    /// the bindings of its free variables.
+    sat: HashMap<(usize, Scope), Rc<lts::StateSet>>,
+    free: HashMap<usize, BTreeSet<mc::VarName>>,
+}
+
+impl Witness {
+    /// Constructs evidence for `f` holding in the initial state if `holds`,
+    /// and for it not holding otherwise.
+    pub fn new(
+        lts: &Lts,
+        f: &mc::Formula,
+        valuation: &mc::Valuation,
+        holds: bool,
+    ) -> Witness {
+        let f = if holds {
+            f.to_pnf()
+        } else {
+            mc::Formula::Not { f: Box::new(f.clone()) }.to_pnf()
+        };
+        let f = f.lower_regular().rename_bound();
+        let mut builder = Builder {
+            lts,
+            valuation,
+            nodes: Vec::new(),
+            visited: HashMap::new(),
+            queue: VecDeque::new(),
+            instances: Vec::new(),
+            entered: HashMap::new(),
+            sat: HashMap::new(),
+            free: HashMap::new(),
+        };
+        builder.node(lts.init(), &f, Vec::new());
+        while let Some((id, f, scope)) = builder.queue.pop_front() {
+            let edges = builder.discharge(builder.nodes[id].state, f, &scope);
+            builder.nodes[id].edges = edges;
+        }
+        Witness { holds, formula: f.to_string(), nodes: builder.nodes }
+    }
+
+    /// Extracts evidence from a solved game for `f` (or for its negation if
+    /// `holds` is false) that is won by `Even` in the initial state.
+    pub fn from_strategy(
+        lts: &Lts,
+        game: &Game,
+        solution: &Solution,
+        holds: bool,
+    ) -> Witness {
+        let root = game.vertex(lts.init());
+        let mut ids = vec![None; game.vertices.len()];
+        let mut nodes = Vec::new();
+        let mut queue = VecDeque::new();
+        let mut discovered = 1;
+        ids[root] = Some(0);
+        queue.push_back(root);
+        while let Some(v) = queue.pop_front() {
+            let (state, i) = game.vertices[v];
+            let moves = match (game.owner[v], solution.strategy[v]) {
+                (Player::Even, Some(w)) => vec![w],
+                _ => game.succ[v].clone(),
+            };
+            let mut edges = Vec::new();
+            // Self-loops only mark vertices in which a player cannot move.
+            for w in moves.into_iter().filter(|&w| w != v) {
+                let target = match ids[w] {
+                    Some(id) => id,
+                    None => {
+                        ids[w] = Some(discovered);
+                        queue.push_back(w);
+                        discovered += 1;
+                        discovered - 1
+                    },
+                };
+                let label = match &game.subformulas[i] {
+                    mc::Formula::Diamond { step, .. }
+                    | mc::Formula::Box { step, .. } => {
+                        let t = game.vertices[w].0;
+                        lts.successors(state)
+                            .find(|(l, u)| *u == t && action(step).matches(l))
+                            .map(|(l, _)| l.clone())
+                    },
+                    _ => None,
+                };
+                edges.push(Edge { label, target });
+            }
+            let formula = game.subformulas[i].to_string();
+            nodes.push(Node { state, formula, edges });
+        }
+        let formula = nodes[0].formula.clone();
+        Witness { holds, formula, nodes }
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.to_string(),
+            Format::Json => serde_json::to_string_pretty(self).unwrap(),
+            Format::Dot => self.to_dot(),
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let mut dot = String::from("digraph witness {\n");
+        for (id, node) in self.nodes.iter().enumerate() {
+            let label = format!("{} ⊨ {}", node.state, node.formula);
+            writeln!(dot, "  n{} [label=\"{}\"];", id, escape(&label)).unwrap();
+            for edge in &node.edges {
+                match &edge.label {
+                    Some(label) => writeln!(
+                        dot,
+                        "  n{} -> n{} [label=\"{}\"];",
+                        id,
+                        edge.target,
+                        escape(label)
+                    ),
+                    None => writeln!(dot, "  n{} -> n{};", id, edge.target),
+                }
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl<'a> Builder<'a> {
+    /// Returns the node for the obligation `s ⊨ f`, creating and enqueueing
+    /// it if it has not been encountered before.
+    fn node(
+        &mut self,
+        s: lts::State,
+        f: &'a mc::Formula,
+        scope: Scope,
+    ) -> usize {
+        let key = (s, f as *const _ as usize, scope);
+        if let Some(&id) = self.visited.get(&key) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            state: s,
+            formula: f.to_string(),
+            edges: vec![],
+        });
+        self.queue.push_back((id, f, key.2.clone()));
+        self.visited.insert(key, id);
+        id
+    }
+
+    fn discharge(
+        &mut self,
+        s: lts::State,
+        f: &'a mc::Formula,
+        scope: &Scope,
+    ) -> Vec<Edge> {
+        use mc::Formula::*;
+        let edge = |target| Edge { label: None, target };
+
+        match f {
+            Var { name } => {
+                let i = match self.position(scope, name) {
+                    Some(i) => i,
+                    None => return Vec::new(),
+                };
+                // Unfolding a variable leaves the scope of everything bound
+                // within its fixpoint.
+                let mut inner = scope[..=i].to_vec();
+                let instance = &self.instances[inner[i].instance];
+                if let Value::Least(ranks) = &instance.value {
+                    inner[i].rank = ranks[&s] - 1;
+                }
+                let body = instance.body;
+                vec![edge(self.node(s, body, inner))]
+            },
+            True | False | Not { .. } => Vec::new(),
+            And { f1, f2 } => vec![
+                edge(self.node(s, f1, scope.clone())),
+                edge(self.node(s, f2, scope.clone())),
+            ],
+            Or { f1, f2 } => {
+                let g = if self.holds(s, f1, scope) { f1 } else { f2 };
+                vec![edge(self.node(s, g, scope.clone()))]
+            },
+            Diamond { step, f: g } => {
+                let act = action(step);
+                let lts = self.lts;
+                let (label, t) = lts
+                    .successors(s)
+                    .find(|&(l, t)| act.matches(l) && self.holds(t, g, scope))
+                    .unwrap();
+                let target = self.node(t, g, scope.clone());
+                vec![Edge { label: Some(label.clone()), target }]
+            },
+            Box { step, f: g } => {
+                let act = action(step);
+                let lts = self.lts;
+                lts.successors(s)
+                    .filter(|(l, _)| act.matches(l))
+                    .map(|(l, t)| Edge {
+                        label: Some(l.clone()),
+                        target: self.node(t, g, scope.clone()),
+                    })
+                    .collect()
+            },
+            Mu { f: g, .. } | Nu { f: g, .. } => {
+                let instance = self.enter(f, scope);
+                let rank = match &self.instances[instance].value {
+                    Value::Least(ranks) => ranks[&s] - 1,
+                    Value::Greatest(_) => 0,
+                };
+                let mut inner = scope.clone();
+                inner.push(Bound { instance, rank });
+                vec![edge(self.node(s, g, inner))]
+            },
+            Implies { .. } | Iff { .. } =>
+                unreachable!("formula is not in positive normal form"),
+        }
+    }
+
+    /// Returns the instance of `fixpoint` in `scope`, computing its value
+    /// when it is entered for the first time. The approximants of a least
+    /// fixpoint are the same in every state, so they are computed once.
+    fn enter(&mut self, fixpoint: &'a mc::Formula, scope: &Scope) -> usize {
+        let key = (fixpoint as *const _ as usize, scope.clone());
+        if let Some(&instance) = self.entered.get(&key) {
+            return instance;
+        }
+        let instance = match fixpoint {
+            mc::Formula::Mu { var, f: body } => {
+                let valuation = self.valuation(scope);
+                let ranks = naive::ranks(self.lts, var, body, &valuation);
+                Instance { var, body, value: Value::Least(ranks) }
+            },
+            mc::Formula::Nu { var, f: body } => {
+                let value = Value::Greatest(self.sat(fixpoint, scope));
+                Instance { var, body, value }
+            },
+            _ => unreachable!("only fixpoints can be entered"),
+        };
+        self.instances.push(instance);
+        self.entered.insert(key, self.instances.len() - 1);
+        self.instances.len() - 1
+    }
+
+    /// Decides whether `s ⊨ f`, following the connectives and modalities of
+    /// `f` down to its fixpoints and variables.
+    fn holds(&mut self, s: lts::State, f: &mc::Formula, scope: &Scope) -> bool {
+        use mc::Formula::*;
+        match f {
+            True => true,
+            False => false,
+            Var { name } => match self.position(scope, name) {
+                Some(i) => self.contains(scope[i], s),
+                None => self.valuation[name].contains(&s),
+            },
+            Not { f } => !self.holds(s, f, scope),
+            And { f1, f2 } =>
+                self.holds(s, f1, scope) && self.holds(s, f2, scope),
+            Or { f1, f2 } =>
+                self.holds(s, f1, scope) || self.holds(s, f2, scope),
+            Diamond { step, f: g } => {
+                let act = action(step);
+                let lts = self.lts;
+                lts.successors(s)
+                    .any(|(l, t)| act.matches(l) && self.holds(t, g, scope))
+            },
+            Box { step, f: g } => {
+                let act = action(step);
+                let lts = self.lts;
+                lts.successors(s)
+                    .all(|(l, t)| !act.matches(l) || self.holds(t, g, scope))
+            },
+            Mu { .. } | Nu { .. } => self.sat(f, scope).contains(&s),
+            Implies { .. } | Iff { .. } =>
+                unreachable!("formula is not in positive normal form"),
+        }
+    }
+
+    fn sat(&mut self, f: &mc::Formula, scope: &Scope) -> Rc<lts::StateSet> {
+        // Only the bindings of the free variables of `f` matter.
+        let ptr = f as *const _ as usize;
+        let free = self.free.entry(ptr).or_insert_with(|| f.free_variables());
+        let instances = &self.instances;
+        let scope = scope
+            .iter()
+            .filter(|b| free.contains(instances[b.instance].var))
+            .cloned()
+            .collect::<Scope>();
+        let key = (ptr, scope);
+        if let Some(sat) = self.sat.get(&key) {
+            return sat.clone();
+        }
+        let valuation = self.valuation(&key.1);
+        let sat = Rc::new(naive::eval(self.lts, f, &valuation).0);
+        self.sat.insert(key, sat.clone());
+        sat
+    }
+
+    /// The innermost binding of `name` in `scope`.
+    fn position(&self, scope: &Scope, name: &mc::VarName) -> Option<usize> {
+        scope.iter().rposition(|b| self.instances[b.instance].var == name)
+    }
+
+    fn contains(&self, bound: Bound, s: lts::State) -> bool {
+        match &self.instances[bound.instance].value {
+            Value::Least(ranks) =>
+                matches!(ranks.get(&s), Some(&rank) if rank <= bound.rank),
+            Value::Greatest(fixpoint) => fixpoint.contains(&s),
+        }
+    }
+
+    fn valuation(&self, scope: &Scope) -> mc::Valuation {
+        let mut valuation = self.valuation.clone();
+        for &bound in scope {
+            let instance = &self.instances[bound.instance];
+            let value = match &instance.value {
+                Value::Least(ranks) => ranks
+                    .iter()
+                    .filter(|&(_, &rank)| rank <= bound.rank)
+                    .map(|(&s, _)| s)
+                    .collect(),
+                Value::Greatest(fixpoint) => (**fixpoint).clone(),
+            };
+            valuation.insert(instance.var.clone(), value);
+        }
+        valuation
+    }
+}
+
+fn action(step: &mc::RegFormula) -> &mc::ActionFormula {
+    match step {
+        mc::RegFormula::Action { act } => act,
+        _ => unreachable!("regular formulas should have been lowered"),
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "dot" => Ok(Format::Dot),
+            _ => Err(format!("unknown witness format {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Witness {
+    /// Prints the evidence as a tree, in which nodes that were already shown
+    /// are referred to by their number.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut shown = vec![false; self.nodes.len()];
+        let mut stack = vec![(0, 0, None)];
+        while let Some((id, depth, label)) = stack.pop() {
+            let node: &Node = &self.nodes[id];
+            write!(fmt, "{:1$}", "", 2 * depth)?;
+            if let Some(label) = label {
+                write!(fmt, "--{}--> ", label)?;
+            }
+            if shown[id] {
+                writeln!(fmt, "#{} (see above)", id)?;
+                continue;
+            }
+            writeln!(fmt, "#{} {} ⊨ {}", id, node.state, node.formula)?;
+            shown[id] = true;
+            for edge in node.edges.iter().rev() {
+                stack.push((edge.target, depth + 1, edge.label.as_ref()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{stats::Stats, zielonka};
+
+    const LTS: &str = r#"des (0,5,4)
+(0,"tau",1)
+(1,"a",2)
+(1,"tau",3)
+(2,"b",0)
+(3,"tau",3)"#;
+
+    fn witness(f: &str, holds: bool) -> Witness {
+        let lts = LTS.parse::<Lts>().unwrap();
+        let f = f.parse::<mc::Formula>().unwrap();
+        let w = Witness::new(&lts, &f, &mc::Valuation::new(), holds);
+        check_consistent(&lts, &w);
+        w
+    }
+
+    /// Checks that every obligation in the witness is valid and that its
+    /// labeled edges follow transitions of the LTS.
+    fn check_consistent(lts: &Lts, w: &Witness) {
+        for node in &w.nodes {
+            let f = node.formula.parse::<mc::Formula>().unwrap();
+            if !f.is_open() {
+                let sat = naive::eval(lts, &f, &mc::Valuation::new()).0;
+                assert!(sat.contains(&node.state), "{} ⊭ {}", node.state, f);
+            }
+            for edge in &node.edges {
+                if let Some(label) = &edge.label {
+                    let target = w.nodes[edge.target].state;
+                    assert!(lts
+                        .successors(node.state)
+                        .any(|(l, t)| l == label && t == target));
+                }
+            }
+        }
+    }
+
+    fn path(w: &Witness) -> Vec<(&str, lts::State)> {
+        let mut path = Vec::new();
+        let mut id = 0;
+        while let Some(edge) = w.nodes[id].edges.first() {
+            if path.len() > w.nodes.len() {
+                break;
+            }
+            id = edge.target;
+            if let Some(label) = &edge.label {
+                path.push((label.as_str(), w.nodes[id].state));
+            }
+        }
+        path
+    }
+
+    #[test]
+    fn witness_path() {
+        let w = witness("<tau*.a.b>true", true);
+        assert_eq!(path(&w), vec![("tau", 1), ("a", 2), ("b", 0)]);
+    }
+
+    #[test]
+    fn counterexample_path() {
+        let w = witness("[tau.tau]<tau*.b>true", false);
+        assert!(!w.holds);
+        assert_eq!(w.formula, "<tau><tau>nu Z. ([b]false && [tau]Z)");
+        assert_eq!(&path(&w)[..2], &[("tau", 1), ("tau", 3)]);
+    }
+
+    #[test]
+    fn lasso() {
+        let w = witness("<tau.tau>nu X. <tau>X", true);
+        let back_edges = w
+            .nodes
+            .iter()
+            .enumerate()
+            .flat_map(|(id, n)| n.edges.iter().map(move |e| (id, e.target)))
+            .filter(|(id, target)| target <= id)
+            .count();
+        assert_eq!(back_edges, 1);
+        assert!(w.to_string().contains("(see above)"));
+    }
+
+    #[test]
+    fn least_fixpoint_chain() {
+        let n = 2_000;
+        let mut aut = format!("des (0,{},{})\n", n, n + 1);
+        for s in 0..n {
+            aut.push_str(&format!("({},\"a\",{})\n", s, s + 1));
+        }
+        let lts = aut.parse::<Lts>().unwrap();
+        let f = "mu X. [a]X".parse::<mc::Formula>().unwrap();
+        let w = Witness::new(&lts, &f, &mc::Valuation::new(), true);
+        check_consistent(&lts, &w);
+        // Every state is visited once, at the rank it enters the fixpoint,
+        // so the evidence is the chain itself without any cycles.
+        assert_eq!(w.nodes.len(), 2 * (n + 1));
+        assert!(w
+            .nodes
+            .iter()
+            .enumerate()
+            .all(|(id, n)| n.edges.iter().all(|e| e.target > id)));
+    }
+
+    #[test]
+    fn strategy() {
+        let lts = LTS.parse::<Lts>().unwrap();
+        let cases = [
+            ("<tau*.a.b>true", true, vec![("tau", 1), ("a", 2), ("b", 0)]),
+            ("![tau.tau]<tau*.b>true", false, vec![("tau", 1), ("tau", 3)]),
+        ];
+        for (f, holds, expected) in &cases {
+            let f = f.parse::<mc::Formula>().unwrap();
+            let game = Game::new(&lts, &f, &mc::Valuation::new());
+            let solution = zielonka::solve(&game, &mut Stats::new());
+            let w = Witness::from_strategy(&lts, &game, &solution, *holds);
+            check_consistent(&lts, &w);
+            assert_eq!(&path(&w)[..expected.len()], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn formats() {
+        let w = witness("<a>true || <tau>true", true);
+        assert_eq!(
+            w.render(Format::Text),
+            "#0 0 ⊨ (<a>true || <tau>true)\n  #1 0 ⊨ <tau>true\n    \
+             --tau--> #2 1 ⊨ true\n"
+        );
+        let json =
+            serde_json::from_str::<serde_json::Value>(&w.render(Format::Json))
+                .unwrap();
+        assert_eq!(json["nodes"][1]["edges"][0]["label"], "tau");
+        assert!(w.render(Format::Dot).contains("n1 -> n2 [label=\"tau\"];"));
+    }
+}
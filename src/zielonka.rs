@@ -0,0 +1,165 @@
+use crate::{
+    checker::{ModelChecker, Options},
+    game::{Game, Player, Solution},
+    lts::{self, Lts},
+    mu_calculus as mc,
+    stats::Stats,
+    witness,
+};
+use std::collections::HashMap;
+
+pub fn eval(
+    lts: &Lts,
+    f: &mc::Formula,
+    valuation: &mc::Valuation,
+) -> (lts::StateSet, Stats) {
+    let game = Game::new(lts, f, valuation);
+    let mut stats = Stats::new();
+    let solution = solve(&game, &mut stats);
+    (game.winning_states(&solution), stats)
+}
+
+pub struct Zielonka;
+
+impl ModelChecker for Zielonka {
+    fn name(&self) -> &'static str {
+        "zielonka"
+    }
+
+    fn description(&self) -> &'static str {
+        "Solves the parity game with Zielonka's recursive algorithm"
+    }
+
+    fn steps(&self) -> &'static str {
+        "recursive calls"
+    }
+
+    fn eval(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+    ) -> (lts::StateSet, Stats) {
+        eval(lts, f, options.valuation)
+    }
+
+    /// Reads the witness off from the winning strategy.
+    fn witness(
+        &self,
+        lts: &Lts,
+        f: &mc::Formula,
+        options: &Options,
+        holds: bool,
+    ) -> witness::Witness {
+        let f = match holds {
+            true => f.clone(),
+            false => mc::Formula::Not { f: Box::new(f.clone()) },
+        };
+        let game = Game::new(lts, &f, options.valuation);
+        let solution = solve(&game, &mut Stats::new());
+        witness::Witness::from_strategy(lts, &game, &solution, holds)
+    }
+}
+
+/// Solves a parity game with Zielonka's recursive algorithm. Every
+/// recursive call counts as a step.
+pub fn solve(game: &Game, stats: &mut Stats) -> Solution {
+    let n = game.vertices.len();
+    let mut strategy = vec![None; n];
+    let [_, odd] = solve_inner(game, &vec![true; n], &mut strategy, stats);
+    let mut winner = vec![Player::Even; n];
+    for v in odd {
+        winner[v] = Player::Odd;
+    }
+    Solution { winner, strategy }
+}
+
+/// Computes the winning regions of the subgame induced by the vertices in
+/// `set`, indexed by player. The strategy is updated for every vertex owned
+/// by the player winning it.
+fn solve_inner(
+    game: &Game,
+    set: &[bool],
+    strategy: &mut [Option<usize>],
+    stats: &mut Stats,
+) -> [Vec<usize>; 2] {
+    let vertices = (0..set.len()).filter(|&v| set[v]).collect::<Vec<_>>();
+    let max = match vertices.iter().map(|&v| game.priority[v]).max() {
+        Some(max) => max,
+        None => return [Vec::new(), Vec::new()],
+    };
+    stats.steps += 1;
+
+    let player = Player::of_priority(max);
+    let opponent = player.opponent();
+    let top = vertices
+        .iter()
+        .cloned()
+        .filter(|&v| game.priority[v] == max)
+        .collect::<Vec<_>>();
+    for &v in &top {
+        if game.owner[v] == player {
+            strategy[v] = game.succ[v].iter().cloned().find(|&w| set[w]);
+        }
+    }
+    let attr = attractor(game, set, &top, player, strategy);
+    let sub = difference(set, &attr);
+    let won = solve_inner(game, &sub, strategy, stats);
+    if won[opponent as usize].is_empty() {
+        let mut won = [Vec::new(), Vec::new()];
+        won[player as usize] = vertices;
+        return won;
+    }
+
+    let attr =
+        attractor(game, set, &won[opponent as usize], opponent, strategy);
+    let sub = difference(set, &attr);
+    let mut won = solve_inner(game, &sub, strategy, stats);
+    won[opponent as usize].extend(vertices.into_iter().filter(|&v| attr[v]));
+    won
+}
+
+/// Computes the vertices in `set` from which `player` can force the play
+/// into `target`, and updates the strategy of `player` accordingly.
+fn attractor(
+    game: &Game,
+    set: &[bool],
+    target: &[usize],
+    player: Player,
+    strategy: &mut [Option<usize>],
+) -> Vec<bool> {
+    let mut attr = vec![false; set.len()];
+    let mut queue = target.to_vec();
+    for &v in target {
+        attr[v] = true;
+    }
+    // The number of moves an opponent's vertex has left to escape.
+    let mut escapes = HashMap::new();
+    while let Some(v) = queue.pop() {
+        for &u in &game.pred[v] {
+            if !set[u] || attr[u] {
+                continue;
+            }
+            if game.owner[u] == player {
+                strategy[u] = Some(v);
+            } else {
+                let escapes = escapes.entry(u).or_insert_with(|| {
+                    game.succ[u].iter().filter(|&&w| set[w]).count()
+                });
+                *escapes -= 1;
+                if *escapes > 0 {
+                    continue;
+                }
+            }
+            attr[u] = true;
+            queue.push(u);
+        }
+    }
+    attr
+}
+
+fn difference(set: &[bool], other: &[bool]) -> Vec<bool> {
+    set.iter().zip(other).map(|(&a, &b)| a && !b).collect()
+}
+
+generate_tests!();